@@ -0,0 +1,32 @@
+use gl_puck::obj::*;
+use std::env;
+use std::path::Path;
+use std::time::Instant;
+use std::{fs, io};
+
+// Usage: cargo run --release --example obj_bench [file.obj]
+fn main() -> io::Result<()> {
+    let file = env::args().nth(1).unwrap_or_else(|| "rungholt.obj".to_owned());
+    let bytes = fs::read(Path::new(&file))?;
+    println!("Loading {} ( {} MB ) on {} threads", file, bytes.len() / (1024 * 1024), rayon::current_num_threads());
+
+    let new_obj = || ObjData::<f32, u32>::new(Dimension::ThreeDim, Some(Dimension::TwoDim), Some(Dimension::ThreeDim));
+
+    let mut sequential = new_obj();
+    let t1 = Instant::now();
+    let sequential_warnings = sequential.load_from_bytes(&bytes, ParseMode::Lenient, None)?;
+    let sequential_time = t1.elapsed().as_secs_f32();
+    println!("Sequential: {} seconds", sequential_time);
+
+    let mut parallel = new_obj();
+    let t1 = Instant::now();
+    let parallel_warnings = parallel.load_parallel_from_bytes(&bytes, ParseMode::Lenient)?;
+    let parallel_time = t1.elapsed().as_secs_f32();
+    println!("Parallel: {} seconds", parallel_time);
+
+    println!("Speedup: {}x", sequential_time / parallel_time);
+    println!("{} vertices, {} indices, {} warnings", sequential.pos_data.len(), sequential.indicies.len(), sequential_warnings.len());
+    assert!(sequential == parallel, "The parallel loader gave a different mesh!");
+    assert!(sequential_warnings == parallel_warnings, "The parallel loader gave different warnings!");
+    Ok(())
+}
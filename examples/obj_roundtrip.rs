@@ -0,0 +1,27 @@
+use gl_puck::obj::*;
+use std::env;
+use std::io;
+use std::path::Path;
+
+// Loads a file, writes it out again and checks that loading the written file gives the same mesh
+// Usage: cargo run --release --example obj_roundtrip [file.obj]
+fn main() -> io::Result<()> {
+    let file = env::args().nth(1).unwrap_or_else(|| "rungholt.obj".to_owned());
+    let new_obj = || ObjData::<f32, u32>::new(Dimension::ThreeDim, Some(Dimension::TwoDim), Some(Dimension::ThreeDim));
+
+    let mut original = new_obj();
+    let warnings = original.load_from_path(Path::new(&file), ParseMode::Lenient, None)?;
+    println!("Loaded {} ( {} vertices, {} indices, {} warnings )", file, original.pos_data.len(), original.indicies.len(), warnings.len());
+
+    let out = env::temp_dir().join("obj_roundtrip.obj");
+    original.save(&out, &WriteOptions::default())?;
+    println!("Saved to {}", out.display());
+
+    let mut reloaded = new_obj();
+    reloaded.load_from_path(&out, ParseMode::Strict, None)?;
+    // save names the material library after the new file
+    reloaded.mtl_libs = original.mtl_libs.clone();
+    assert!(original == reloaded, "Loading the saved file gave a different mesh!");
+    println!("Round trip gave the same mesh");
+    Ok(())
+}
@@ -1,8 +1,11 @@
 use gl::types::*;
+use gl_puck::cache::CacheScalar;
 use gl_puck::camera::Camera3D;
 use gl_puck::input::KeyboardHandler;
+use gl_puck::layout::Attribute;
 use gl_puck::model::{Model, World3D};
 use gl_puck::obj::*;
+use gl_puck::optimize::OptimizeOptions;
 use gl_puck::{mesh, model};
 use gl_wrapper::render::{program, shader, texture};
 use gl_wrapper::util::{buffer_obj, aggregator_obj};
@@ -13,85 +16,11 @@ use glutin::event_loop::{ControlFlow, EventLoop};
 use glutin::platform::run_return::EventLoopExtRunReturn;
 use glutin::window::WindowBuilder;
 use std::convert::{TryFrom, TryInto};
-use std::fs::File;
-use std::num::ParseFloatError;
-use std::path::Path;
-use std::str::FromStr;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use std::{fs, io};
 
-fn normalise_and_center(pos: &mut Vec<f32>) -> &mut Vec<f32> {
-    let mut ma: f32 = 0.0;
-    let mut mi: f32 = pos[0];
-    // Assumes ma != mi
-    for e in pos.iter() {
-        if *e > ma {
-            ma = *e;
-        }
-        if *e < mi {
-            mi = *e;
-        }
-    }
-    let b: f32 = ma - mi;
-    pos.iter_mut().for_each(|e| *e = (*e - mi) / b - 0.5); // Now everything is between -0.5 and 0.5
-    pos
-}
-
-fn invert_tex(tex: &mut Vec<f32>) -> &mut Vec<f32> {
-    let mut a = false;
-    tex.iter_mut().for_each(|e| {
-        if a {
-            *e = 1.0 - *e
-        }
-        a = !a;
-    });
-    tex
-}
-
-use std::hash::Hash;
-#[derive(Clone, Copy, Default)]
-struct F32Wrapper(pub f32);
-
-impl F32Wrapper{
-    fn key(&self) -> u32{
-       self.0.to_bits()
-    }
-}
-
-impl PartialEq for F32Wrapper{
-    fn eq(&self, other: &Self) -> bool {
-        self.key() == other.key()
-    }
-}
-
-impl Eq for F32Wrapper{}
-impl Hash for F32Wrapper{
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.key().hash(state);
-    }
-}
-
-impl FromStr for F32Wrapper{
-    type Err = ParseFloatError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        f32::from_str(s).map(|v| Self(v))
-    }
-}
-
-impl From<f32> for F32Wrapper{
-    fn from(v: f32) -> Self {
-        Self(v)
-    }
-}
-
-impl From<F32Wrapper> for f32{
-    fn from(v: F32Wrapper) -> Self{
-        v.0
-    }
-}
-
-
 // TODO: Add better input handling( simpler than glutin )( look at create gilrs for controller input maybe ), maybe obscure the glutin event loop a bit more, figure out why movement is jittery even though i'm adapting speed to deltaT in-between frames ( possibly not my fault and it's just that the timer might be inaccurate but it seems way too off for that )
 fn main() -> io::Result<()> {
     let mut prog_bouncer = program::ProgramBouncer::new();
@@ -105,6 +34,8 @@ fn main() -> io::Result<()> {
     let mut w_width: u32 = 400;
     let mut w_height: u32 = 400;
     const OBJ_FILE: &str = "dragon.obj";
+    // Parsed version of OBJ_FILE, rewritten whenever it's older than OBJ_FILE
+    const CACHE_FILE: &str = "dragon.mesh";
     const FRAGMENT_SHADER_FILE: &str = "fragmentShader.glsl";
     const VERTEX_SHADER_FILE: &str = "vertexShader.glsl";
     const TEXTURE_FILE: &str = "rungholt-RGBA.png";
@@ -129,35 +60,90 @@ fn main() -> io::Result<()> {
     let mut cam = Camera3D::new();
 
     println!("Loading obj ...");
-    let (pos_vbo, tex_vbo, ind_ibo) = {
-        let mut o = ObjData::<F32Wrapper, u32>::new(Dimension::ThreeDim, Some(Dimension::TwoDim), None);
-        {
-            let t1 = Instant::now();
-            o.load(&mut File::open(OBJ_FILE)?)?;
-            println!(
-                "Took {} seconds to load obj file!",
+    let (vertex_vbo, vertex_layout, ind_ibo, draw_ranges, texture_files) = {
+        let mut o = ObjData::<f32, u32>::new(Dimension::ThreeDim, Some(Dimension::TwoDim), None);
+        let modified = |f: &str| fs::metadata(f).and_then(|m| m.modified()).ok();
+        let cache_is_fresh = match (modified(CACHE_FILE), modified(OBJ_FILE)) {
+            (Some(cache), Some(obj)) => cache >= obj,
+            (Some(_), None) => true,
+            _ => false
+        };
+        let t1 = Instant::now();
+        let cache_res = if cache_is_fresh { o.load_cache_from_path(Path::new(CACHE_FILE)).map_err(|e| e.to_string()) } else { Err("it's out of date".to_owned()) };
+        match cache_res {
+            Ok(()) => println!(
+                "Took {} seconds to load cache file!",
                 t1.elapsed().as_secs_f32()
-            );
+            ),
+            Err(e) => {
+                println!("Not using the cache file ( {} )", e);
+                let mut print_progress = |p: LoadProgress| {
+                    if let Some(total) = p.total_bytes {
+                        println!("{}%", p.bytes_read * 100 / total.max(1));
+                    }
+                };
+                let warnings = o.load_from_path(Path::new(OBJ_FILE), ParseMode::Lenient, Some(&mut print_progress))?;
+                println!(
+                    "Took {} seconds to load obj file!",
+                    t1.elapsed().as_secs_f32()
+                );
+                for w in warnings {
+                    println!("Warning: {}", w);
+                }
+                // Done before caching, so that loading the cache gives the optimised order too
+                let t2 = Instant::now();
+                match o.optimize(&OptimizeOptions::default()) {
+                    Ok(r) => println!(
+                        "Took {} seconds to optimise the index order, ACMR {:.3} -> {:.3}",
+                        t2.elapsed().as_secs_f32(), r.acmr_before, r.acmr_after
+                    ),
+                    Err(e) => println!("Warning: failed to optimise the index order: {}", e)
+                }
+                if let Err(e) = o.save_cache(Path::new(CACHE_FILE), CacheScalar::F32) {
+                    println!("Warning: failed to write cache file: {}", e);
+                }
+            }
+        }
+        for w in o.load_mtl_libs(Path::new(OBJ_FILE).parent().unwrap_or(Path::new(".")), ParseMode::Lenient)? {
+            println!("Warning: {}", w);
+        }
+        // Texture 0 is TEXTURE_FILE, it's used for everything that has no material or no diffuse map
+        let mut texture_files: Vec<PathBuf> = vec![PathBuf::from(TEXTURE_FILE)];
+        let mut draw_ranges: Vec<(Range<usize>, usize)> = Vec::new();
+        let mut drawn_until = 0;
+        for r in &o.material_ranges {
+            if r.range.start > drawn_until {
+                draw_ranges.push((drawn_until..r.range.start, 0));
+            }
+            let tex = match r.material.and_then(|m| o.materials[m].diffuse_map.clone()) {
+                Some(p) => match texture_files.iter().position(|f| *f == p) {
+                    Some(i) => i,
+                    None => { texture_files.push(p); texture_files.len() - 1 }
+                },
+                None => 0
+            };
+            draw_ranges.push((r.range.clone(), tex));
+            drawn_until = r.range.end;
         }
-        let mut actual_pos_data: Vec<f32> =  o.pos_data.get_vals().iter().map(|v|f32::from(*v)).collect();
-        let mut actual_tex_data: Vec<f32> =  o.tex_data.expect("Texture mapping data should be there since we requested it.").get_vals().iter().map(|v|f32::from(*v)).collect();
+        if drawn_until < o.indicies.len() {
+            draw_ranges.push((drawn_until..o.indicies.len(), 0));
+        }
+        o.fit_to_unit_cube();
+        o.flip_v();
+        let vertices = o.interleave::<GLfloat>(&[Attribute::Position, Attribute::TexCoord]).expect("Texture mapping data should be there since we requested it.");
         (
             buffer_obj::VBO::<GLfloat>::with_data(
                 &mut vbo_bouncer,
-                &[3],
-                normalise_and_center(&mut actual_pos_data).as_slice(),
+                &vertices.layout.components(),
+                vertices.data.as_slice(),
                 gl::STATIC_DRAW,
             )
-            .expect("Failed to create pos_vbo!"),
-            buffer_obj::VBO::<GLfloat>::with_data(
-                &mut vbo_bouncer,
-                &[2],
-                invert_tex(&mut actual_tex_data).as_slice(),
-                gl::STATIC_DRAW,
-            )
-            .expect("Failed to create tex_vbo!"),
+            .expect("Failed to create vertex_vbo!"),
+            vertices.layout,
             buffer_obj::IBO::<GLuint>::with_data(&mut ibo_bouncer, &o.indicies.as_slice(), gl::STATIC_DRAW)
                 .expect("Failed to create ind_ibo!"),
+            draw_ranges,
+            texture_files,
         )
     };
     println!("Done!");
@@ -179,11 +165,11 @@ fn main() -> io::Result<()> {
     {let id = program.get_sampler_id("obj_tex").unwrap().try_into().unwrap(); program.set_uniform_i32(id, 0);}
     let mut tex_bouncer = texture::TextureBouncer::<0>::new();
 
-    let mut t = {
-        let im = image::open(&Path::new(TEXTURE_FILE))
+    let mut textures: Vec<texture::Texture2D> = texture_files.iter().map(|f| {
+        let im = image::open(f)
             .expect("Failed to load image!")
             .into_rgba8();
-        texture::Texture2D::with_data(
+        let mut t = texture::Texture2D::with_data(
                 &mut tex_bouncer,
         [
                 im.width().try_into().unwrap(),
@@ -192,11 +178,14 @@ fn main() -> io::Result<()> {
             im.as_ref(),
             gl::RGBA,
         )
-        .expect("Failed to create texture")
-    };
-    let mut t = t.bind_mut(&mut tex_bouncer);
-    t.set_mag_filter_of_bound_tex(gl::NEAREST);
-    t.set_min_filter_of_bound_tex(gl::NEAREST);
+        .expect("Failed to create texture");
+        {
+            let mut t = t.bind_mut(&mut tex_bouncer);
+            t.set_mag_filter_of_bound_tex(gl::NEAREST);
+            t.set_min_filter_of_bound_tex(gl::NEAREST);
+        }
+        t
+    }).collect();
 
     let mut model = {
         let m = mesh::UnboundMesh::new(&ind_ibo);
@@ -204,23 +193,9 @@ fn main() -> io::Result<()> {
     };
     let mut model = model.bind(&mut vao_bouncer, &mut ibo_bouncer);
     // Prepare model for use with program
-    let pos_vbo = pos_vbo.bind(&mut vbo_bouncer);
-    model
-        .adapt_model_to_attrib(
-            &pos_vbo,
-            program
-                .get_attribute_id("position")
-                .expect("Attribute not loaded!"),
-        )
-        .unwrap();
-    let tex_vbo = tex_vbo.bind(&mut vbo_bouncer);
+    let vertex_vbo = vertex_vbo.bind(&mut vbo_bouncer);
     model
-        .adapt_model_to_attrib(
-            &tex_vbo,
-            program
-                .get_attribute_id("tex_coord")
-                .expect("Attribute not loaded!"),
-        )
+        .adapt_model_to_layout(&vertex_vbo, &vertex_layout, &program)
         .unwrap();
     model.adapt_model_to_program(&program).unwrap();
 
@@ -343,7 +318,10 @@ fn main() -> io::Result<()> {
                             (proj * *cam.get_mat() * model_mat).as_ref(),
                         );
                     }
-                    model.render(&program).unwrap();
+                    for (r, tex) in &draw_ranges {
+                        let _t = textures[*tex].bind_mut(&mut tex_bouncer);
+                        model.render_range(&program, r.clone()).unwrap();
+                    }
                     gl_window.swap_buffers().unwrap();
                     unsafe {
                         gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
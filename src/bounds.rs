@@ -0,0 +1,201 @@
+use crate::obj::{ObjData, ObjScalar};
+use crate::vector::{add_scaled, distance, sub};
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl Aabb {
+    /// None if there are no points
+    pub fn from_points(points: &[[f64; 3]]) -> Option<Self> {
+        let first = *points.first()?;
+        let mut b = Self{ min: first, max: first };
+        for p in &points[1..] {
+            for (k, v) in p.iter().enumerate() {
+                b.min[k] = b.min[k].min(*v);
+                b.max[k] = b.max[k].max(*v);
+            }
+        }
+        Some(b)
+    }
+
+    pub fn center(&self) -> [f64; 3] {
+        [(self.min[0] + self.max[0]) / 2.0, (self.min[1] + self.max[1]) / 2.0, (self.min[2] + self.max[2]) / 2.0]
+    }
+
+    /// Width, height and depth
+    pub fn size(&self) -> [f64; 3] {
+        [self.max[0] - self.min[0], self.max[1] - self.min[1], self.max[2] - self.min[2]]
+    }
+
+    /// The biggest of width, height and depth
+    pub fn longest_side(&self) -> f64 {
+        let s = self.size();
+        s[0].max(s[1]).max(s[2])
+    }
+
+    pub fn contains(&self, p: [f64; 3]) -> bool {
+        (0..3).all(|k| self.min[k] <= p[k] && p[k] <= self.max[k])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f64; 3],
+    pub radius: f64,
+}
+
+fn farthest_from(points: &[[f64; 3]], from: [f64; 3]) -> [f64; 3] {
+    points.iter().copied().fold(from, |best, p| if distance(p, from) > distance(best, from) { p } else { best })
+}
+
+impl BoundingSphere {
+    /// An approximate bounding sphere ( Ritter's ), not the minimal one. None if there are no points.
+    pub fn from_points(points: &[[f64; 3]]) -> Option<Self> {
+        let first = *points.first()?;
+        // Start with the two points that are ( roughly ) the farthest apart
+        let a = farthest_from(points, first);
+        let b = farthest_from(points, a);
+        let mut s = Self{ center: [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0], radius: distance(a, b) / 2.0 };
+        // Grow it just enough to touch every point that is outside
+        for p in points {
+            let d = distance(*p, s.center);
+            if d > s.radius {
+                let new_radius = (s.radius + d) / 2.0;
+                let t = (new_radius - s.radius) / d;
+                s.center = add_scaled(s.center, sub(*p, s.center), t);
+                s.radius = new_radius;
+            }
+        }
+        // Rounding can leave the points it grew to just outside
+        s.radius = points.iter().map(|p| distance(*p, s.center)).fold(s.radius, f64::max);
+        Some(s)
+    }
+
+    pub fn contains(&self, p: [f64; 3]) -> bool {
+        distance(p, self.center) <= self.radius
+    }
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default {
+    /// Positions as xyz, a missing y or z is 0 and w is ignored
+    pub(crate) fn positions_3d(&self) -> Vec<[f64; 3]> {
+        self.pos_data.iter().map(|p| {
+            [p[0].to_f64(), p.get(1).map_or(0.0, |y| y.to_f64()), p.get(2).map_or(0.0, |z| z.to_f64())]
+        }).collect()
+    }
+
+    /// Bounding box of all positions, None if there are none
+    pub fn aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(&self.positions_3d())
+    }
+
+    /// An approximate ( not minimal ) sphere around all positions, see `BoundingSphere::from_points`, None if there are none
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        BoundingSphere::from_points(&self.positions_3d())
+    }
+
+    /// Moves every position by offset, components the positions don't have are ignored
+    pub fn translate(&mut self, offset: [f64; 3]) {
+        for p in self.pos_data.iter_mut() {
+            for (v, o) in p.iter_mut().zip(&offset) {
+                *v = T::from_f64(v.to_f64() + o);
+            }
+        }
+    }
+
+    /// Scales every position by factor around the origin ( normals and tangents stay the same )
+    pub fn scale(&mut self, factor: f64) {
+        for p in self.pos_data.iter_mut() {
+            for v in p.iter_mut().take(3) {
+                *v = T::from_f64(v.to_f64() * factor);
+            }
+        }
+    }
+
+    /// Moves the mesh so that the centre of its bounding box is at the origin
+    pub fn center_at_origin(&mut self) {
+        if let Some(b) = self.aabb() {
+            let c = b.center();
+            self.translate([-c[0], -c[1], -c[2]]);
+        }
+    }
+
+    /// Centres the mesh and scales its longest side to 1 ( a mesh that is only a point is only centred )
+    pub fn fit_to_unit_cube(&mut self) {
+        let b = match self.aabb() {
+            Some(b) => b,
+            None => return
+        };
+        let c = b.center();
+        self.translate([-c[0], -c[1], -c[2]]);
+        let side = b.longest_side();
+        if side > 0.0 && side.is_finite() {
+            self.scale(1.0 / side);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::Dimension;
+
+    /// Reproducible points in a stretched, shifted cloud
+    fn cloud(n: usize) -> Vec<[f64; 3]> {
+        let mut seed: u64 = 7;
+        let mut next = || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+        (0..n).map(|_| [next() * 4.0 - 1.0, next() * 2.0 + 3.0, next() - 0.5]).collect()
+    }
+
+    #[test]
+    fn aabb_of_points() {
+        assert_eq!(Aabb::from_points(&[]), None);
+        let b = Aabb::from_points(&[[1.0, -2.0, 0.0], [-1.0, 4.0, 0.5], [0.0, 0.0, 3.0]]).unwrap();
+        assert_eq!((b.min, b.max), ([-1.0, -2.0, 0.0], [1.0, 4.0, 3.0]));
+        assert_eq!((b.center(), b.size(), b.longest_side()), ([0.0, 1.0, 1.5], [2.0, 6.0, 3.0], 6.0));
+        assert!(b.contains([1.0, 4.0, 3.0]) && !b.contains([0.0, 4.1, 1.0]));
+    }
+
+    #[test]
+    fn bounding_sphere_contains_every_point() {
+        assert_eq!(BoundingSphere::from_points(&[]), None);
+        let single = BoundingSphere::from_points(&[[1.0, 2.0, 3.0]]).unwrap();
+        assert_eq!((single.center, single.radius), ([1.0, 2.0, 3.0], 0.0));
+
+        for n in [2, 10, 1000] {
+            let points = cloud(n);
+            let s = BoundingSphere::from_points(&points).unwrap();
+            assert!(points.iter().all(|p| s.contains(*p)), "{} points", n);
+            // It can't be smaller than half the distance of the two points farthest apart, nor much bigger than the box
+            let b = Aabb::from_points(&points).unwrap();
+            let widest = points.iter().flat_map(|a| points.iter().map(move |b| distance(*a, *b))).fold(0.0, f64::max);
+            assert!(s.radius >= widest / 2.0 && s.radius <= distance(b.min, b.max) / 2.0 * 1.2, "{} points", n);
+        }
+    }
+
+    #[test]
+    fn fit_to_unit_cube() {
+        let mut o: ObjData<f32, u32> = ObjData::new(Dimension::ThreeDim, None, None);
+        o.pos_data.data = cloud(100).iter().flatten().map(|v| *v as f32).collect();
+        o.fit_to_unit_cube();
+        let b = o.aabb().unwrap();
+        assert!((b.longest_side() - 1.0).abs() < 1e-6);
+        assert!(b.center().iter().all(|c| c.abs() < 1e-6));
+        // The proportions stay the same
+        let size = b.size();
+        assert!(size[0] > size[1] && size[1] > size[2]);
+
+        // A single point is only moved to the origin
+        o.pos_data.data = vec![2.0, 3.0, 4.0, 2.0, 3.0, 4.0];
+        o.fit_to_unit_cube();
+        assert_eq!(o.pos_data.data, [0.0; 6]);
+    }
+}
@@ -0,0 +1,622 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt,
+    fs::File,
+    io::{self, Write},
+    mem,
+    ops::Range,
+    path::Path,
+};
+
+use memmap2::Mmap;
+
+use crate::obj::{Dimension, MaterialRange, ObjData, ObjScalar, SubMesh, VertexAttribs};
+
+// Layout of a cache file, everything is little-endian:
+//   0  magic "PUCKMESH"
+//   8  version: u32
+//  12  scalar size, index size, pos dim, tex dim, norm dim, tangent dim, color dim: u8 each ( a dim of 0 means the attribute isn't there )
+//  19  1 reserved byte
+//  20  reserved: u32
+//  24  vertex count: u64
+//  32  index count: u64
+//  40  line index count: u64
+//  48  point index count: u64
+//  56  reserved: u64
+//  64  positions, texture coordinates, normals, tangents, colours, indices, line indices and point indices, each starting at a multiple of ARRAY_ALIGN
+//      then mtllib names, material ranges and sub-meshes ( see `write_names` )
+const MAGIC: &[u8; 8] = b"PUCKMESH";
+pub const CACHE_VERSION: u32 = 1;
+/// Alignment of the arrays in the file, for memory-mapping
+const ARRAY_ALIGN: usize = 16;
+
+/// How vertex data is stored in the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheScalar {
+    F32 = 4,
+    F64 = 8,
+}
+
+/// f32 or f64, what the vertex data of a `MeshCache` can be viewed as
+pub trait CacheFloat: bytemuck::Pod {
+    const SCALAR: CacheScalar;
+}
+
+impl CacheFloat for f32 {
+    const SCALAR: CacheScalar = CacheScalar::F32;
+}
+
+impl CacheFloat for f64 {
+    const SCALAR: CacheScalar = CacheScalar::F64;
+}
+
+/// An unsigned integer, what the indices of a `MeshCache` can be viewed as
+pub trait CacheIndex: bytemuck::Pod {}
+
+impl CacheIndex for u8 {}
+impl CacheIndex for u16 {}
+impl CacheIndex for u32 {}
+impl CacheIndex for u64 {}
+
+/// bytes as S without copying, None on big-endian hosts or if they aren't aligned for S ( a memory map always is )
+fn view<S: bytemuck::Pod>(bytes: &[u8]) -> Option<&[S]> {
+    if cfg!(target_endian = "big") {
+        return None;
+    }
+    bytemuck::try_cast_slice(bytes).ok()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+    Io(io::ErrorKind),
+    /// The file doesn't start with the magic bytes
+    NotACache,
+    /// The file was written by a different version of the format
+    UnsupportedVersion(u32),
+    /// The file ends before all of the data that the header describes
+    Truncated,
+    /// A header field has a value that the format doesn't allow
+    InvalidHeader(&'static str),
+    /// The file stores a different layout than the `ObjData` it's loaded into
+    LayoutMismatch { what: &'static str, expected: String, found: String },
+    /// An index in the file doesn't fit into the index type
+    IndicesTooBig,
+    /// An index in the file points past the last vertex
+    IndexOutOfRange(usize),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Io(k) => write!(f, "io error: {:?}", k),
+            CacheError::NotACache => write!(f, "not a mesh cache file"),
+            CacheError::UnsupportedVersion(v) => write!(f, "mesh cache version {} isn't supported ( expected version {} )", v, CACHE_VERSION),
+            CacheError::Truncated => write!(f, "mesh cache file is truncated"),
+            CacheError::InvalidHeader(what) => write!(f, "invalid {} in mesh cache header", what),
+            CacheError::LayoutMismatch { what, expected, found } => write!(f, "mesh cache has {} {}, expected {}", what, found, expected),
+            CacheError::IndicesTooBig => write!(f, "indices in mesh cache don't fit into the index type"),
+            CacheError::IndexOutOfRange(i) => write!(f, "index {} in mesh cache is out of range", i),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<io::Error> for CacheError {
+    fn from(e: io::Error) -> Self {
+        CacheError::Io(e.kind())
+    }
+}
+
+impl From<CacheError> for io::Error {
+    fn from(e: CacheError) -> Self {
+        match e {
+            CacheError::Io(k) => io::Error::new(k, e.to_string()),
+            _ => io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+        }
+    }
+}
+
+fn dim_from_u8(d: u8, what: &'static str) -> Result<Option<Dimension>, CacheError> {
+    match d {
+        0 => Ok(None),
+        1 => Ok(Some(Dimension::OneDim)),
+        2 => Ok(Some(Dimension::TwoDim)),
+        3 => Ok(Some(Dimension::ThreeDim)),
+        4 => Ok(Some(Dimension::FourDim)),
+        _ => Err(CacheError::InvalidHeader(what))
+    }
+}
+
+fn dim_to_string(d: Option<Dimension>) -> String {
+    match d {
+        Some(d) => format!("{}D", d as usize),
+        None => "none".to_owned()
+    }
+}
+
+fn align(offset: usize) -> usize {
+    offset.div_ceil(ARRAY_ALIGN) * ARRAY_ALIGN
+}
+
+/// Reads a cache file in order, failing if it ends too early
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CacheError> {
+        let end = self.pos.checked_add(len).filter(|e| *e <= self.bytes.len()).ok_or(CacheError::Truncated)?;
+        let s = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(s)
+    }
+
+    fn u8(&mut self) -> Result<u8, CacheError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, CacheError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, CacheError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, CacheError> {
+        usize::try_from(self.u64()?).map_err(|_| CacheError::Truncated)
+    }
+
+    fn string(&mut self) -> Result<String, CacheError> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn range(&mut self) -> Result<Range<usize>, CacheError> {
+        let start = self.usize()?;
+        let end = self.usize()?;
+        if start > end {
+            return Err(CacheError::InvalidHeader("range"));
+        }
+        Ok(start..end)
+    }
+}
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    w.write_all(&(s.len() as u32).to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn write_range<W: Write>(w: &mut W, r: &Range<usize>) -> io::Result<()> {
+    w.write_all(&(r.start as u64).to_le_bytes())?;
+    w.write_all(&(r.end as u64).to_le_bytes())
+}
+
+/// A checked but not copied cache file, its arrays are raw little-endian values
+#[derive(Debug, Clone)]
+pub struct MeshCache<'a> {
+    pub scalar: CacheScalar,
+    /// Size of an index in bytes
+    pub index_size: usize,
+    pub pos_dim: Dimension,
+    pub tex_dim: Option<Dimension>,
+    pub norm_dim: Option<Dimension>,
+    pub tangent_dim: Option<Dimension>,
+    pub color_dim: Option<Dimension>,
+    pub vertex_count: usize,
+    pub index_count: usize,
+    pub line_index_count: usize,
+    pub point_index_count: usize,
+    pub mtl_libs: Vec<String>,
+    /// Not linked to materials yet, see `ObjData::load_mtl_libs`
+    pub material_ranges: Vec<MaterialRange>,
+    pub sub_meshes: Vec<SubMesh>,
+    pos: &'a [u8],
+    tex: Option<&'a [u8]>,
+    norm: Option<&'a [u8]>,
+    tangent: Option<&'a [u8]>,
+    color: Option<&'a [u8]>,
+    indicies: &'a [u8],
+    line_indicies: &'a [u8],
+    point_indicies: &'a [u8],
+}
+
+impl<'a> MeshCache<'a> {
+    /// Checks the header and that the file contains everything it describes
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, CacheError> {
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(CacheError::NotACache);
+        }
+        let mut r = Reader{ bytes, pos: MAGIC.len() };
+        let version = r.u32()?;
+        if version != CACHE_VERSION {
+            return Err(CacheError::UnsupportedVersion(version));
+        }
+        let scalar = match r.u8()? {
+            4 => CacheScalar::F32,
+            8 => CacheScalar::F64,
+            _ => return Err(CacheError::InvalidHeader("scalar size"))
+        };
+        let index_size = match r.u8()? {
+            s @ 1 | s @ 2 | s @ 4 | s @ 8 => s as usize,
+            _ => return Err(CacheError::InvalidHeader("index size"))
+        };
+        let pos_dim = dim_from_u8(r.u8()?, "position dimension")?.ok_or(CacheError::InvalidHeader("position dimension"))?;
+        let tex_dim = dim_from_u8(r.u8()?, "texture coordinate dimension")?;
+        let norm_dim = dim_from_u8(r.u8()?, "normal dimension")?;
+        let tangent_dim = dim_from_u8(r.u8()?, "tangent dimension")?;
+        let color_dim = dim_from_u8(r.u8()?, "colour dimension")?;
+        r.take(5)?;
+        let vertex_count = r.usize()?;
+        let index_count = r.usize()?;
+        let line_index_count = r.usize()?;
+        let point_index_count = r.usize()?;
+        r.take(8)?;
+
+        let scalar_size = scalar as usize;
+        let array = |r: &mut Reader<'a>, count: usize, elem_size: usize| -> Result<&'a [u8], CacheError> {
+            r.pos = align(r.pos);
+            let len = count.checked_mul(elem_size).ok_or(CacheError::Truncated)?;
+            r.take(len)
+        };
+        let pos = array(&mut r, vertex_count, pos_dim as usize * scalar_size)?;
+        let tex = match tex_dim { Some(d) => Some(array(&mut r, vertex_count, d as usize * scalar_size)?), None => None };
+        let norm = match norm_dim { Some(d) => Some(array(&mut r, vertex_count, d as usize * scalar_size)?), None => None };
+        let tangent = match tangent_dim { Some(d) => Some(array(&mut r, vertex_count, d as usize * scalar_size)?), None => None };
+        let color = match color_dim { Some(d) => Some(array(&mut r, vertex_count, d as usize * scalar_size)?), None => None };
+        let indicies = array(&mut r, index_count, index_size)?;
+        let line_indicies = array(&mut r, line_index_count, index_size)?;
+        let point_indicies = array(&mut r, point_index_count, index_size)?;
+
+        let mut mtl_libs: Vec<String> = Vec::new();
+        for _ in 0..r.u32()? {
+            mtl_libs.push(r.string()?);
+        }
+        let mut material_ranges: Vec<MaterialRange> = Vec::new();
+        for _ in 0..r.u32()? {
+            let name = r.string()?;
+            material_ranges.push(MaterialRange{ name, material: None, range: r.range()? });
+        }
+        let mut sub_meshes: Vec<SubMesh> = Vec::new();
+        for _ in 0..r.u32()? {
+            let object = r.string()?;
+            let group = r.string()?;
+            let material_name = if r.u8()? != 0 { Some(r.string()?) } else { None };
+            sub_meshes.push(SubMesh{ object, group, material_name, material: None, range: r.range()? });
+        }
+        if material_ranges.iter().map(|m| &m.range).chain(sub_meshes.iter().map(|m| &m.range)).any(|r| r.end > index_count) {
+            return Err(CacheError::InvalidHeader("range"));
+        }
+
+        Ok(Self{ scalar, index_size, pos_dim, tex_dim, norm_dim, tangent_dim, color_dim, vertex_count, index_count, line_index_count, point_index_count, mtl_libs, material_ranges, sub_meshes, pos, tex, norm, tangent, color, indicies, line_indicies, point_indicies })
+    }
+
+    pub fn pos_bytes(&self) -> &'a [u8] { self.pos }
+    pub fn tex_bytes(&self) -> Option<&'a [u8]> { self.tex }
+    pub fn norm_bytes(&self) -> Option<&'a [u8]> { self.norm }
+    pub fn tangent_bytes(&self) -> Option<&'a [u8]> { self.tangent }
+    pub fn color_bytes(&self) -> Option<&'a [u8]> { self.color }
+    pub fn index_bytes(&self) -> &'a [u8] { self.indicies }
+    pub fn line_index_bytes(&self) -> &'a [u8] { self.line_indicies }
+    pub fn point_index_bytes(&self) -> &'a [u8] { self.point_indicies }
+
+    /// The positions without copying, e.g. to upload them straight from the map, None if S isn't the stored scalar
+    pub fn pos_as<S: CacheFloat>(&self) -> Option<&'a [S]> { self.floats(Some(self.pos)) }
+    pub fn tex_as<S: CacheFloat>(&self) -> Option<&'a [S]> { self.floats(self.tex) }
+    pub fn norm_as<S: CacheFloat>(&self) -> Option<&'a [S]> { self.floats(self.norm) }
+    pub fn tangent_as<S: CacheFloat>(&self) -> Option<&'a [S]> { self.floats(self.tangent) }
+    pub fn color_as<S: CacheFloat>(&self) -> Option<&'a [S]> { self.floats(self.color) }
+    /// The indices without copying, None if J isn't the stored index size
+    pub fn indicies_as<J: CacheIndex>(&self) -> Option<&'a [J]> { self.ints(self.indicies) }
+    pub fn line_indicies_as<J: CacheIndex>(&self) -> Option<&'a [J]> { self.ints(self.line_indicies) }
+    pub fn point_indicies_as<J: CacheIndex>(&self) -> Option<&'a [J]> { self.ints(self.point_indicies) }
+
+    fn floats<S: CacheFloat>(&self, bytes: Option<&'a [u8]>) -> Option<&'a [S]> {
+        bytes.filter(|_| S::SCALAR == self.scalar).and_then(view)
+    }
+
+    fn ints<J: CacheIndex>(&self, bytes: &'a [u8]) -> Option<&'a [J]> {
+        // Indices aren't checked against the vertex count here, unlike in `ObjData::load_cache`
+        Some(bytes).filter(|_| mem::size_of::<J>() == self.index_size).and_then(view)
+    }
+}
+
+/// Memory-maps a cache file for `MeshCache::parse`, it must not be changed while it's mapped
+pub fn map_file(path: &Path) -> io::Result<Mmap> {
+    let f = File::open(path)?;
+    // Safety: the caller promises not to change the file while the map is alive, like the docs above say
+    unsafe { Mmap::map(&f) }
+}
+
+fn read_scalars<T: ObjScalar>(bytes: &[u8], scalar: CacheScalar) -> Vec<T> {
+    match scalar {
+        CacheScalar::F32 => bytes.chunks_exact(4).map(|b| T::from_f64(f32::from_le_bytes(b.try_into().unwrap()) as f64)).collect(),
+        CacheScalar::F64 => bytes.chunks_exact(8).map(|b| T::from_f64(f64::from_le_bytes(b.try_into().unwrap()))).collect(),
+    }
+}
+
+fn write_scalars<T: ObjScalar, W: Write>(w: &mut W, data: &[T], scalar: CacheScalar) -> io::Result<()> {
+    for v in data {
+        match scalar {
+            CacheScalar::F32 => w.write_all(&(v.to_f64() as f32).to_le_bytes())?,
+            CacheScalar::F64 => w.write_all(&v.to_f64().to_le_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+/// Keeps track of how much was written, to pad arrays to ARRAY_ALIGN
+struct CountingWriter<W> {
+    inner: W,
+    written: usize,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn pad(&mut self) -> io::Result<()> {
+        let padding = align(self.written) - self.written;
+        self.write_all(&[0u8; ARRAY_ALIGN][..padding])
+    }
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy + TryInto<usize> + TryFrom<usize>{
+    /// Writes the already deduplicated mesh in the cache format, materials, smoothing and normal generation settings aren't stored
+    pub fn write_cache<W: Write>(&self, w: W, scalar: CacheScalar) -> io::Result<()>{
+        let index_size = mem::size_of::<I>();
+        if ![1, 2, 4, 8].contains(&index_size) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("indices of {} bytes can't be cached", index_size)));
+        }
+        let vertex_count = self.pos_data.len();
+        let dim_of = |a: &Option<VertexAttribs<T>>| -> io::Result<u8> {
+            match a {
+                Some(a) if a.len() != vertex_count =>
+                    Err(io::Error::new(io::ErrorKind::InvalidInput, "every vertex attribute needs one element per position")),
+                Some(a) => Ok(a.get_elem_per_vert() as u8),
+                None => Ok(0)
+            }
+        };
+
+        let mut w = CountingWriter{ inner: w, written: 0 };
+        w.write_all(MAGIC)?;
+        w.write_all(&CACHE_VERSION.to_le_bytes())?;
+        w.write_all(&[scalar as u8, index_size as u8, self.pos_data.get_elem_per_vert() as u8, dim_of(&self.tex_data)?, dim_of(&self.norm_data)?, dim_of(&self.tangent_data)?, dim_of(&self.color_data)?, 0])?;
+        w.write_all(&0u32.to_le_bytes())?;
+        w.write_all(&(vertex_count as u64).to_le_bytes())?;
+        w.write_all(&(self.indicies.len() as u64).to_le_bytes())?;
+        w.write_all(&(self.line_indicies.len() as u64).to_le_bytes())?;
+        w.write_all(&(self.point_indicies.len() as u64).to_le_bytes())?;
+        w.write_all(&0u64.to_le_bytes())?;
+
+        for a in [Some(&self.pos_data), self.tex_data.as_ref(), self.norm_data.as_ref(), self.tangent_data.as_ref(), self.color_data.as_ref()].iter().flatten() {
+            w.pad()?;
+            write_scalars(&mut w, &a.data, scalar)?;
+        }
+        for indicies in [&self.indicies, &self.line_indicies, &self.point_indicies] {
+            w.pad()?;
+            for i in indicies {
+                let i: usize = (*i).try_into().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "index doesn't fit into usize"))?;
+                w.write_all(&(i as u64).to_le_bytes()[..index_size])?;
+            }
+        }
+        self.write_names(&mut w)?;
+        w.flush()
+    }
+
+    /// u32 count and elements of the mtllib names, material ranges ( two u64s ) and sub-meshes, strings are a u32 length and UTF-8
+    fn write_names<W: Write>(&self, w: &mut W) -> io::Result<()>{
+        w.write_all(&(self.mtl_libs.len() as u32).to_le_bytes())?;
+        for l in &self.mtl_libs {
+            write_string(w, l)?;
+        }
+        w.write_all(&(self.material_ranges.len() as u32).to_le_bytes())?;
+        for r in &self.material_ranges {
+            write_string(w, &r.name)?;
+            write_range(w, &r.range)?;
+        }
+        w.write_all(&(self.sub_meshes.len() as u32).to_le_bytes())?;
+        for m in &self.sub_meshes {
+            write_string(w, &m.object)?;
+            write_string(w, &m.group)?;
+            match &m.material_name {
+                Some(name) => { w.write_all(&[1])?; write_string(w, name)?; },
+                None => w.write_all(&[0])?
+            }
+            write_range(w, &m.range)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_cache(&self, path: &Path, scalar: CacheScalar) -> io::Result<()>{
+        self.write_cache(io::BufWriter::new(File::create(path)?), scalar)
+    }
+
+    /// Copies the mesh out of cache, which must have the same attribute dimensions and index size ( use the `*_as` views of `MeshCache` to upload without copying )
+    pub fn load_cache(&mut self, cache: &MeshCache) -> Result<(), CacheError>{
+        let mismatch = |what, expected: String, found: String| Err(CacheError::LayoutMismatch{ what, expected, found });
+        if cache.pos_dim != self.pos_data.get_elem_per_vert() {
+            return mismatch("positions", dim_to_string(Some(self.pos_data.get_elem_per_vert())), dim_to_string(Some(cache.pos_dim)));
+        }
+        let tex_dim = self.tex_data.as_ref().map(|t| t.get_elem_per_vert());
+        if cache.tex_dim != tex_dim {
+            return mismatch("texture coordinates", dim_to_string(tex_dim), dim_to_string(cache.tex_dim));
+        }
+        let norm_dim = self.norm_data.as_ref().map(|n| n.get_elem_per_vert());
+        if cache.norm_dim != norm_dim {
+            return mismatch("normals", dim_to_string(norm_dim), dim_to_string(cache.norm_dim));
+        }
+        if cache.index_size != mem::size_of::<I>() {
+            return mismatch("indices of", format!("{} bytes", mem::size_of::<I>()), format!("{} bytes", cache.index_size));
+        }
+
+        let attribs = |bytes: &[u8], dim: Dimension| {
+            let mut a = VertexAttribs::new(dim);
+            a.data = read_scalars(bytes, cache.scalar);
+            a
+        };
+        let read_indicies = |bytes: &[u8]| -> Result<Vec<I>, CacheError> {
+            let mut indicies: Vec<I> = Vec::with_capacity(bytes.len() / cache.index_size);
+            for b in bytes.chunks_exact(cache.index_size) {
+                let mut le = [0u8; 8];
+                le[..b.len()].copy_from_slice(b);
+                let i = usize::try_from(u64::from_le_bytes(le)).map_err(|_| CacheError::IndicesTooBig)?;
+                if i >= cache.vertex_count {
+                    return Err(CacheError::IndexOutOfRange(i));
+                }
+                indicies.push(I::try_from(i).map_err(|_| CacheError::IndicesTooBig)?);
+            }
+            Ok(indicies)
+        };
+        let indicies = read_indicies(cache.indicies)?;
+        let line_indicies = read_indicies(cache.line_indicies)?;
+        let point_indicies = read_indicies(cache.point_indicies)?;
+
+        self.pos_data = attribs(cache.pos, cache.pos_dim);
+        self.tex_data = cache.tex.zip(cache.tex_dim).map(|(b, d)| attribs(b, d));
+        self.norm_data = cache.norm.zip(cache.norm_dim).map(|(b, d)| attribs(b, d));
+        self.tangent_data = cache.tangent.zip(cache.tangent_dim).map(|(b, d)| attribs(b, d));
+        self.color_data = cache.color.zip(cache.color_dim).map(|(b, d)| attribs(b, d));
+        self.indicies = indicies;
+        self.line_indicies = line_indicies;
+        self.point_indicies = point_indicies;
+        self.mtl_libs = cache.mtl_libs.clone();
+        self.materials.clear();
+        self.material_ranges = cache.material_ranges.clone();
+        self.sub_meshes = cache.sub_meshes.clone();
+        Ok(())
+    }
+
+    /// Memory-maps the cache file at path and copies it into the mesh, see `load_cache`
+    pub fn load_cache_from_path(&mut self, path: &Path) -> Result<(), CacheError>{
+        let map = map_file(path)?;
+        self.load_cache(&MeshCache::parse(&map)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::ParseMode;
+
+    const HOUSE: &str = "mtllib house.mtl\n\
+        v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0.5 1.5 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+        o house\ng walls\nusemtl brick\nf 1/1/1 2/2/1 3/3/1 5/4/1 4/4/1\n\
+        g roof\nusemtl tiles\nf 4/1/1 3/2/1 5/3/1\nl 1 2\np 5\n";
+
+    fn house<I: std::str::FromStr + Copy + Send + Sync + TryInto<usize> + TryFrom<usize>>() -> ObjData<f32, I> {
+        let mut o = ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), Some(Dimension::ThreeDim));
+        o.load_from_bytes(HOUSE.as_bytes(), ParseMode::Strict, None).unwrap();
+        o
+    }
+
+    fn cached<I: Copy + TryInto<usize> + TryFrom<usize>>(o: &ObjData<f32, I>, scalar: CacheScalar) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        o.write_cache(&mut bytes, scalar).unwrap();
+        bytes
+    }
+
+    fn empty() -> ObjData<f32, u32> {
+        ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), Some(Dimension::ThreeDim))
+    }
+
+    #[test]
+    fn round_trip_through_a_mapped_file() {
+        let o = house::<u32>();
+        assert!(!o.line_indicies.is_empty() && !o.point_indicies.is_empty());
+        let path = std::env::temp_dir().join(format!("gl-puck-cache-{}.puck", std::process::id()));
+        o.save_cache(&path, CacheScalar::F32).unwrap();
+
+        let mut loaded = empty();
+        loaded.load_cache_from_path(&path).unwrap();
+        assert!(loaded == o);
+
+        // The mapped arrays can be used as they are
+        let map = map_file(&path).unwrap();
+        let cache = MeshCache::parse(&map).unwrap();
+        assert_eq!(cache.mtl_libs, ["house.mtl"]);
+        assert_eq!(cache.pos_as::<f32>().unwrap(), &o.pos_data.data[..]);
+        assert_eq!(cache.tex_as::<f32>().unwrap(), &o.tex_data.as_ref().unwrap().data[..]);
+        assert_eq!(cache.indicies_as::<u32>().unwrap(), &o.indicies[..]);
+        assert_eq!(cache.line_indicies_as::<u32>().unwrap(), &o.line_indicies[..]);
+        assert_eq!(cache.point_indicies_as::<u32>().unwrap(), &o.point_indicies[..]);
+        assert!(cache.tangent_as::<f32>().is_none() && cache.color_as::<f32>().is_none());
+        drop(map);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn bad_magic_and_unsupported_versions() {
+        let bytes = cached(&house::<u32>(), CacheScalar::F32);
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(MeshCache::parse(&bad).unwrap_err(), CacheError::NotACache);
+        assert_eq!(MeshCache::parse(b"PUCK").unwrap_err(), CacheError::NotACache);
+
+        let mut newer = bytes;
+        newer[8..12].copy_from_slice(&(CACHE_VERSION + 1).to_le_bytes());
+        assert_eq!(MeshCache::parse(&newer).unwrap_err(), CacheError::UnsupportedVersion(CACHE_VERSION + 1));
+    }
+
+    #[test]
+    fn truncated_files() {
+        let bytes = cached(&house::<u32>(), CacheScalar::F64);
+        assert!(MeshCache::parse(&bytes).is_ok());
+        for len in MAGIC.len()..bytes.len() {
+            assert_eq!(MeshCache::parse(&bytes[..len]).unwrap_err(), CacheError::Truncated, "cut at {}", len);
+        }
+    }
+
+    #[test]
+    fn scalar_and_layout_mismatches() {
+        let o = house::<u16>();
+        let bytes = cached(&o, CacheScalar::F64);
+        let cache = MeshCache::parse(&bytes).unwrap();
+        assert_eq!((cache.scalar, cache.index_size), (CacheScalar::F64, 2));
+
+        // Views need the stored types, copying converts the scalar but not the index size
+        assert!(cache.pos_as::<f32>().is_none());
+        assert!(cache.indicies_as::<u32>().is_none());
+        match empty().load_cache(&cache).unwrap_err() {
+            CacheError::LayoutMismatch{ what, .. } => assert_eq!(what, "indices of"),
+            e => panic!("{:?}", e)
+        }
+        let mut loaded: ObjData<f32, u16> = ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), Some(Dimension::ThreeDim));
+        loaded.load_cache(&cache).unwrap();
+        assert!(loaded == o);
+
+        let mut no_normals: ObjData<f32, u16> = ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), None);
+        match no_normals.load_cache(&cache).unwrap_err() {
+            CacheError::LayoutMismatch{ what, .. } => assert_eq!(what, "normals"),
+            e => panic!("{:?}", e)
+        }
+    }
+
+    #[test]
+    fn out_of_range_indices_and_ranges() {
+        let mut o = house::<u32>();
+        o.indicies[0] = 99;
+        let bytes = cached(&o, CacheScalar::F32);
+        assert_eq!(empty().load_cache(&MeshCache::parse(&bytes).unwrap()).unwrap_err(), CacheError::IndexOutOfRange(99));
+
+        let end = o.indicies.len() + 3;
+        let mut o = house::<u32>();
+        o.sub_meshes[1].range.end = end;
+        assert_eq!(MeshCache::parse(&cached(&o, CacheScalar::F32)).unwrap_err(), CacheError::InvalidHeader("range"));
+        let mut o = house::<u32>();
+        o.material_ranges[0].range.end = end;
+        assert_eq!(MeshCache::parse(&cached(&o, CacheScalar::F32)).unwrap_err(), CacheError::InvalidHeader("range"));
+    }
+}
@@ -0,0 +1,199 @@
+use crate::obj::{Dimension, ObjData, ObjScalar, VertexAttribs};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X = 0,
+    Y = 1,
+    Z = 2,
+}
+
+/// Several convention changes at once, see `ObjData::convert`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    /// Positions are multiplied by this, e.g. 0.01 for a file in centimetres when the app uses metres
+    pub unit_scale: f64,
+    pub z_up_to_y_up: bool,
+    pub flip_handedness: bool,
+    pub flip_v: bool,
+    pub reverse_winding: bool,
+}
+
+impl Default for Conversion {
+    fn default() -> Self {
+        Self{ unit_scale: 1.0, z_up_to_y_up: false, flip_handedness: false, flip_v: false, reverse_winding: false }
+    }
+}
+
+/// Replaces the xyz of every element, attributes with less than 3 components are left alone
+fn map_xyz<T: ObjScalar + Default>(attribs: &mut VertexAttribs<T>, f: impl Fn([f64; 3]) -> [f64; 3]) {
+    if (attribs.get_elem_per_vert() as usize) < 3 {
+        return;
+    }
+    for e in attribs.iter_mut() {
+        let v = f([e[0].to_f64(), e[1].to_f64(), e[2].to_f64()]);
+        e[0] = T::from_f64(v[0]);
+        e[1] = T::from_f64(v[1]);
+        e[2] = T::from_f64(v[2]);
+    }
+}
+
+/// Negates component k of every element, if the attribute has it
+fn negate<T: ObjScalar + Default>(attribs: &mut VertexAttribs<T>, k: usize) {
+    if k >= attribs.get_elem_per_vert() as usize {
+        return;
+    }
+    for e in attribs.iter_mut() {
+        e[k] = T::from_f64(-e[k].to_f64());
+    }
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy {
+    /// Applies every change that c asks for, scaling first and reversing the winding last
+    pub fn convert(&mut self, c: &Conversion) {
+        if c.unit_scale != 1.0 {
+            self.scale(c.unit_scale);
+        }
+        if c.z_up_to_y_up {
+            self.z_up_to_y_up();
+        }
+        if c.flip_handedness {
+            self.flip_handedness();
+        }
+        if c.flip_v {
+            self.flip_v();
+        }
+        if c.reverse_winding {
+            self.reverse_winding();
+        }
+    }
+
+    /// v becomes 1 - v, for textures whose rows are stored top to bottom. Tangent handedness is flipped with it.
+    pub fn flip_v(&mut self) {
+        if let Some(t) = &mut self.tex_data {
+            if t.get_elem_per_vert() != Dimension::OneDim {
+                for e in t.iter_mut() {
+                    e[1] = T::from_f64(1.0 - e[1].to_f64());
+                }
+            }
+        }
+        if let Some(t) = &mut self.tangent_data {
+            negate(t, 3);
+        }
+    }
+
+    /// Rotates the mesh so that +z ( e.g. Blender's up ) becomes +y
+    pub fn z_up_to_y_up(&mut self) {
+        let rotate = |v: [f64; 3]| [v[0], v[2], -v[1]];
+        self.map_directions(rotate);
+    }
+
+    /// The opposite of `z_up_to_y_up`
+    pub fn y_up_to_z_up(&mut self) {
+        let rotate = |v: [f64; 3]| [v[0], -v[2], v[1]];
+        self.map_directions(rotate);
+    }
+
+    /// Negates one axis, reversing the winding and the tangent handedness with it
+    pub fn mirror(&mut self, axis: Axis) {
+        self.negate_axis(axis);
+        self.reverse_winding();
+    }
+
+    /// Converts between right-handed ( obj, OpenGL ) and left-handed ( `look_at_lh` ) coordinates by negating z,
+    /// the winding is kept since the coordinate system flips along with the mesh
+    pub fn flip_handedness(&mut self) {
+        self.negate_axis(Axis::Z);
+    }
+
+    /// Negates one axis of positions, normals and tangents, and the tangent handedness
+    fn negate_axis(&mut self, axis: Axis) {
+        negate(&mut self.pos_data, axis as usize);
+        if let Some(n) = &mut self.norm_data {
+            negate(n, axis as usize);
+        }
+        if let Some(t) = &mut self.tangent_data {
+            negate(t, axis as usize);
+            negate(t, 3);
+        }
+    }
+
+    /// Swaps clockwise and counter-clockwise triangles, for exporters that wind the other way. Normals aren't changed.
+    pub fn reverse_winding(&mut self) {
+        for t in self.indicies.chunks_exact_mut(3) {
+            t.swap(1, 2);
+        }
+    }
+
+    /// Applies f to positions, normals and the xyz of tangents
+    fn map_directions(&mut self, f: impl Fn([f64; 3]) -> [f64; 3]) {
+        map_xyz(&mut self.pos_data, &f);
+        if let Some(n) = &mut self.norm_data {
+            map_xyz(n, &f);
+        }
+        if let Some(t) = &mut self.tangent_data {
+            map_xyz(t, &f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One triangle with every attribute that conversions touch
+    fn triangle() -> ObjData<f32, u32> {
+        let mut o = ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), Some(Dimension::ThreeDim));
+        o.pos_data.data = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0];
+        o.tex_data.as_mut().unwrap().data = vec![0.0, 0.25, 1.0, 0.0, 0.5, 1.0];
+        o.norm_data.as_mut().unwrap().data = [0.0, 0.0, 1.0].repeat(3);
+        let mut tangents = VertexAttribs::new(Dimension::FourDim);
+        tangents.data = [1.0, 0.0, 0.0, 1.0].repeat(3);
+        o.tangent_data = Some(tangents);
+        o.indicies = vec![0, 1, 2];
+        o
+    }
+
+    #[test]
+    fn flip_v_flips_tangent_handedness() {
+        let mut o = triangle();
+        o.flip_v();
+        assert_eq!(o.tex_data.as_ref().unwrap().data, [0.0, 0.75, 1.0, 1.0, 0.5, 0.0]);
+        assert_eq!(o.tangent_data.as_ref().unwrap().data, [1.0, 0.0, 0.0, -1.0].repeat(3));
+        assert_eq!(o.pos_data, triangle().pos_data);
+    }
+
+    #[test]
+    fn z_up_to_y_up_and_back() {
+        let mut o = triangle();
+        o.z_up_to_y_up();
+        assert_eq!(o.pos_data.data[..3], [1.0, 3.0, -2.0]);
+        // Up stays up
+        assert_eq!(o.norm_data.as_ref().unwrap().data[..3], [0.0, 1.0, 0.0]);
+        assert_eq!(o.tangent_data.as_ref().unwrap().data[..4], [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(o.indicies, [0, 1, 2]);
+        o.y_up_to_z_up();
+        assert!(o == triangle());
+    }
+
+    #[test]
+    fn mirror_and_flip_handedness() {
+        let mut o = triangle();
+        o.mirror(Axis::X);
+        assert_eq!(o.pos_data.data[..3], [-1.0, 2.0, 3.0]);
+        assert_eq!(o.tangent_data.as_ref().unwrap().data[..4], [-1.0, 0.0, 0.0, -1.0]);
+        assert_eq!(o.indicies, [0, 2, 1]);
+
+        let mut o = triangle();
+        o.flip_handedness();
+        assert_eq!(o.pos_data.data[..3], [1.0, 2.0, -3.0]);
+        assert_eq!(o.norm_data.as_ref().unwrap().data[..3], [0.0, 0.0, -1.0]);
+        assert_eq!(o.tangent_data.as_ref().unwrap().data[..4], [1.0, 0.0, 0.0, -1.0]);
+        assert_eq!(o.indicies, [0, 1, 2]);
+
+        // convert reverses the winding only when asked to
+        let mut o = triangle();
+        o.convert(&Conversion{ flip_handedness: true, reverse_winding: true, ..Conversion::default() });
+        assert_eq!(o.indicies, [0, 2, 1]);
+    }
+}
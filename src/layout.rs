@@ -0,0 +1,122 @@
+use std::mem;
+
+use crate::obj::{ObjData, ObjError, ObjErrorKind, ObjScalar, VertexAttribs};
+
+/// The vertex attributes of `ObjData` that can go into an interleaved buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    Position,
+    TexCoord,
+    Normal,
+    Tangent,
+    Color,
+}
+
+impl Attribute {
+    pub const ALL: [Attribute; 5] = [Attribute::Position, Attribute::TexCoord, Attribute::Normal, Attribute::Tangent, Attribute::Color];
+
+    /// The shader attribute `BoundMesh::adapt_mesh_to_layout` binds it to
+    pub fn shader_name(&self) -> &'static str {
+        match self {
+            Attribute::Position => "position",
+            Attribute::TexCoord => "tex_coord",
+            Attribute::Normal => "normal",
+            Attribute::Tangent => "tangent",
+            Attribute::Color => "color",
+        }
+    }
+}
+
+/// Where one attribute is inside every vertex
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttribLayout {
+    pub attribute: Attribute,
+    /// In bytes, from the start of the vertex
+    pub offset: usize,
+    pub components: usize,
+}
+
+/// Describes the vertices of an interleaved buffer, every component has the type of the buffer's elements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLayout {
+    /// Bytes from the start of one vertex to the start of the next
+    pub stride: usize,
+    /// Bytes per component
+    pub component_size: usize,
+    /// In the order they are in each vertex
+    pub attribs: Vec<AttribLayout>,
+}
+
+impl VertexLayout {
+    pub fn get(&self, attribute: Attribute) -> Option<&AttribLayout> {
+        self.attribs.iter().find(|a| a.attribute == attribute)
+    }
+
+    /// Components per attribute, in order, like `VBO::with_data` wants them
+    pub fn components(&self) -> Vec<usize> {
+        self.attribs.iter().map(|a| a.components).collect()
+    }
+
+    /// Every attribute starts where the one before it ends and the stride is the size of all of them, like `ObjData::interleave` makes it
+    pub fn is_packed(&self) -> bool {
+        let mut offset = 0;
+        for a in &self.attribs {
+            if a.offset != offset {
+                return false;
+            }
+            offset += a.components * self.component_size;
+        }
+        offset == self.stride
+    }
+}
+
+/// One buffer with all attributes of a vertex next to each other, see `ObjData::interleave`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterleavedVertices<S> {
+    pub data: Vec<S>,
+    pub layout: VertexLayout,
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default {
+    fn attribs_of(&self, attribute: Attribute) -> Option<&VertexAttribs<T>> {
+        match attribute {
+            Attribute::Position => Some(&self.pos_data),
+            Attribute::TexCoord => self.tex_data.as_ref(),
+            Attribute::Normal => self.norm_data.as_ref(),
+            Attribute::Tangent => self.tangent_data.as_ref(),
+            Attribute::Color => self.color_data.as_ref(),
+        }
+    }
+
+    /// Every attribute the mesh has, in the order of `Attribute::ALL`
+    pub fn available_attributes(&self) -> Vec<Attribute> {
+        Attribute::ALL.iter().copied().filter(|a| self.attribs_of(*a).is_some()).collect()
+    }
+
+    /// The given attributes of every vertex next to each other, converted to S
+    pub fn interleave<S: ObjScalar>(&self, attributes: &[Attribute]) -> Result<InterleavedVertices<S>, ObjError> {
+        let vertex_count = self.pos_data.len();
+        let mut sources: Vec<(&VertexAttribs<T>, usize)> = Vec::with_capacity(attributes.len());
+        let mut attribs: Vec<AttribLayout> = Vec::with_capacity(attributes.len());
+        let mut offset = 0;
+        for a in attributes {
+            let src = match self.attribs_of(*a) {
+                Some(src) if src.len() == vertex_count => src,
+                _ => return Err(ObjError::new(0, "", ObjErrorKind::MissingAttribute(a.shader_name())))
+            };
+            let components = src.get_elem_per_vert() as usize;
+            sources.push((src, components));
+            attribs.push(AttribLayout{ attribute: *a, offset: offset * mem::size_of::<S>(), components });
+            offset += components;
+        }
+
+        let mut data: Vec<S> = Vec::with_capacity(vertex_count * offset);
+        for i in 0..vertex_count {
+            for (src, components) in &sources {
+                data.extend(src.data[i * components..(i + 1) * components].iter().map(|v| S::from_f64(v.to_f64())));
+            }
+        }
+        Ok(InterleavedVertices{ data, layout: VertexLayout{ stride: offset * mem::size_of::<S>(), component_size: mem::size_of::<S>(), attribs } })
+    }
+}
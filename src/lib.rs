@@ -1,10 +1,24 @@
 use glam::{Mat3, Mat4};
 
+pub mod bounds;
+pub mod cache;
 pub mod camera;
+pub mod conventions;
 pub mod input;
+pub mod layout;
 pub mod mesh;
+pub mod mtl;
 pub mod model;
+pub mod normals;
 pub mod obj;
+pub mod optimize;
+pub mod ply;
+pub mod scene;
+pub mod simplify;
+pub mod stl;
+pub mod tangents;
+pub mod triangulate;
+mod vector;
 
 // TODO: This is a hack, usually you would use a mat4 but i don't want to waste 7 floats
 // I know a bit stupid but i'll figure something better out soon(tm)
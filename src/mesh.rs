@@ -1,5 +1,6 @@
 use std::convert::TryInto;
-use std::ptr;
+use std::ops::Range;
+use std::{mem, ptr};
 
 use gl::types::*;
 use gl_wrapper::render::program::Program;
@@ -9,10 +10,17 @@ use gl_wrapper::HasGLEnum;
 use gl_wrapper::util::aggregator_obj::*;
 use gl_wrapper::util::buffer_obj::*;
 
+use crate::layout::VertexLayout;
+use crate::obj::IndexBuffer;
+
 pub struct UnboundMesh<'a, IT>
 where IT: HasGLEnum{
     unbound_vao: UnboundVAO,
-    unbound_indicies: &'a UnboundIBO<IT>
+    unbound_indicies: &'a UnboundIBO<IT>,
+    /// Only this part of the indices is rendered, e.g. for several meshes sharing one IBO
+    range: Option<Range<usize>>,
+    /// What the indices are drawn as, gl::TRIANGLES unless set with `with_mode`
+    mode: GLenum
 }
 
 impl<'a, IT> UnboundMesh<'a, IT>
@@ -20,15 +28,35 @@ where IT: HasGLEnum{
     pub fn new(vert_ord: &'a UnboundIBO<IT>) -> Self{
         Self{
             unbound_vao: VAO::new(),
-            unbound_indicies: vert_ord
+            unbound_indicies: vert_ord,
+            range: None,
+            mode: gl::TRIANGLES
+        }
+    }
+
+    /// A mesh made of only part of the indices, e.g. one of the sub-meshes of an obj file
+    pub fn with_range(vert_ord: &'a UnboundIBO<IT>, range: Range<usize>) -> Self{
+        Self{
+            unbound_vao: VAO::new(),
+            unbound_indicies: vert_ord,
+            range: Some(range),
+            mode: gl::TRIANGLES
         }
     }
 
+    /// Draws the indices as something other than triangles, e.g. gl::LINES for `ObjData::line_indicies` or gl::POINTS for `ObjData::point_indicies`
+    pub fn with_mode(mut self, mode: GLenum) -> Self{
+        self.mode = mode;
+        self
+    }
+
     pub fn bind<'b>(&'b mut self, bn1: &'b mut VAOBouncer, bn2: &'b mut IBOBouncer) -> BoundMesh<'b, IT>{
         // bind vao and ibo
         BoundMesh{
             vao: self.unbound_vao.bind_mut(bn1),
-            indices: self.unbound_indicies.bind(bn2)
+            indices: self.unbound_indicies.bind(bn2),
+            range: self.range.clone(),
+            mode: self.mode
         }
     }
 
@@ -43,6 +71,8 @@ where
     // /// Unused for now but might use in the future plus i want each mesh to be specific to the type of data used in attributes
     //attrib: PhantomData<AT>,
     indices: BoundIBO<'b, IT>,
+    range: Option<Range<usize>>,
+    mode: GLenum,
 }
 
 impl<'a, 'b, IT> BoundMesh<'b, IT>
@@ -73,18 +103,173 @@ where
         Ok(())
     }
 
+    /// Attaches every attribute of an interleaved VBO ( created with `VertexLayout::components` ) to the shader attribute named by `Attribute::shader_name`, if p has it
+    pub fn adapt_mesh_to_layout<AT>(&mut self, att: &VBO<AT>, layout: &VertexLayout, p: &Program) -> Result<(), ()>
+    where
+        AT: HasGLEnum,
+    {
+        // The vbo only knows the components of each attribute, so that's all the layout may say
+        if layout.component_size != mem::size_of::<AT>() || !layout.is_packed() {
+            return Err(());
+        }
+        for (i, a) in layout.attribs.iter().enumerate() {
+            if let Some(loc) = p.get_attribute_id(a.attribute.shader_name()) {
+                let ind = i.try_into().map_err(|_| ())?;
+                self.vao.attach_vbo_to_vao(att, loc, ind, false).map_err(|_| ())?;
+            }
+        }
+        Ok(())
+    }
+
 
     pub fn render_mesh_with_program(self: &Self, _prg: &Program) -> Result<(), ()>
     where
         IT: HasGLEnum,
     {
+        if let Some(r) = &self.range {
+            return self.render_mesh_range_with_program(_prg, r.clone());
+        }
         let s: i32 = match self.indices.get_size().try_into() {
             Ok(v) => v,
             Err(_) => return Err(()),
         };
         unsafe {
-            gl::DrawElements(gl::TRIANGLES, s, IT::get_gl_type(), ptr::null());
+            gl::DrawElements(self.mode, s, IT::get_gl_type(), ptr::null());
+        }
+        Ok(())
+    }
+    /// Renders only part of the indices, e.g. one of the material ranges of an obj file
+    pub fn render_mesh_range_with_program(self: &Self, _prg: &Program, range: Range<usize>) -> Result<(), ()>
+    where
+        IT: HasGLEnum,
+    {
+        let size: usize = match self.indices.get_size().try_into() {
+            Ok(v) => v,
+            Err(_) => return Err(()),
+        };
+        if range.start > range.end || range.end > size {
+            return Err(());
+        }
+        let s: i32 = match range.len().try_into() {
+            Ok(v) => v,
+            Err(_) => return Err(()),
+        };
+        unsafe {
+            gl::DrawElements(self.mode, s, IT::get_gl_type(), (range.start * mem::size_of::<IT>()) as *const GLvoid);
         }
         Ok(())
     }
 }
+
+/// An IBO with whichever index type an `IndexBuffer` picked
+pub enum AnyIBO {
+    U8(UnboundIBO<GLubyte>),
+    U16(UnboundIBO<GLushort>),
+    U32(UnboundIBO<GLuint>),
+}
+
+impl AnyIBO {
+    pub fn with_data(bn: &mut IBOBouncer, indicies: &IndexBuffer, usage: GLenum) -> Result<Self, ()> {
+        match indicies {
+            IndexBuffer::U8(v) => IBO::<GLubyte>::with_data(bn, v, usage).map(Self::U8).map_err(|_| ()),
+            IndexBuffer::U16(v) => IBO::<GLushort>::with_data(bn, v, usage).map(Self::U16).map_err(|_| ()),
+            IndexBuffer::U32(v) => IBO::<GLuint>::with_data(bn, v, usage).map(Self::U32).map_err(|_| ()),
+        }
+    }
+}
+
+/// `UnboundMesh` over an `AnyIBO`, so the index type can be picked at runtime
+pub enum AnyUnboundMesh<'a> {
+    U8(UnboundMesh<'a, GLubyte>),
+    U16(UnboundMesh<'a, GLushort>),
+    U32(UnboundMesh<'a, GLuint>),
+}
+
+impl<'a> AnyUnboundMesh<'a> {
+    pub fn new(vert_ord: &'a AnyIBO) -> Self {
+        match vert_ord {
+            AnyIBO::U8(i) => Self::U8(UnboundMesh::new(i)),
+            AnyIBO::U16(i) => Self::U16(UnboundMesh::new(i)),
+            AnyIBO::U32(i) => Self::U32(UnboundMesh::new(i)),
+        }
+    }
+
+    pub fn with_range(vert_ord: &'a AnyIBO, range: Range<usize>) -> Self {
+        match vert_ord {
+            AnyIBO::U8(i) => Self::U8(UnboundMesh::with_range(i, range)),
+            AnyIBO::U16(i) => Self::U16(UnboundMesh::with_range(i, range)),
+            AnyIBO::U32(i) => Self::U32(UnboundMesh::with_range(i, range)),
+        }
+    }
+
+    /// See `UnboundMesh::with_mode`
+    pub fn with_mode(self, mode: GLenum) -> Self {
+        match self {
+            Self::U8(m) => Self::U8(m.with_mode(mode)),
+            Self::U16(m) => Self::U16(m.with_mode(mode)),
+            Self::U32(m) => Self::U32(m.with_mode(mode)),
+        }
+    }
+
+    pub fn bind<'b>(&'b mut self, bn1: &'b mut VAOBouncer, bn2: &'b mut IBOBouncer) -> AnyBoundMesh<'b> {
+        match self {
+            Self::U8(m) => AnyBoundMesh::U8(m.bind(bn1, bn2)),
+            Self::U16(m) => AnyBoundMesh::U16(m.bind(bn1, bn2)),
+            Self::U32(m) => AnyBoundMesh::U32(m.bind(bn1, bn2)),
+        }
+    }
+}
+
+pub enum AnyBoundMesh<'b> {
+    U8(BoundMesh<'b, GLubyte>),
+    U16(BoundMesh<'b, GLushort>),
+    U32(BoundMesh<'b, GLuint>),
+}
+
+impl<'b> AnyBoundMesh<'b> {
+    pub fn adapt_mesh_to_program(&mut self, p: &Program) -> Result<(), ()> {
+        match self {
+            Self::U8(m) => m.adapt_mesh_to_program(p),
+            Self::U16(m) => m.adapt_mesh_to_program(p),
+            Self::U32(m) => m.adapt_mesh_to_program(p),
+        }
+    }
+
+    pub fn adapt_mesh_to_attrib<AT>(&mut self, att: &VBO<AT>, att_loc: GLuint) -> Result<(), ()>
+    where
+        AT: HasGLEnum,
+    {
+        match self {
+            Self::U8(m) => m.adapt_mesh_to_attrib(att, att_loc),
+            Self::U16(m) => m.adapt_mesh_to_attrib(att, att_loc),
+            Self::U32(m) => m.adapt_mesh_to_attrib(att, att_loc),
+        }
+    }
+
+    pub fn adapt_mesh_to_layout<AT>(&mut self, att: &VBO<AT>, layout: &VertexLayout, p: &Program) -> Result<(), ()>
+    where
+        AT: HasGLEnum,
+    {
+        match self {
+            Self::U8(m) => m.adapt_mesh_to_layout(att, layout, p),
+            Self::U16(m) => m.adapt_mesh_to_layout(att, layout, p),
+            Self::U32(m) => m.adapt_mesh_to_layout(att, layout, p),
+        }
+    }
+
+    pub fn render_mesh_with_program(&self, prg: &Program) -> Result<(), ()> {
+        match self {
+            Self::U8(m) => m.render_mesh_with_program(prg),
+            Self::U16(m) => m.render_mesh_with_program(prg),
+            Self::U32(m) => m.render_mesh_with_program(prg),
+        }
+    }
+
+    pub fn render_mesh_range_with_program(&self, prg: &Program, range: Range<usize>) -> Result<(), ()> {
+        match self {
+            Self::U8(m) => m.render_mesh_range_with_program(prg, range),
+            Self::U16(m) => m.render_mesh_range_with_program(prg, range),
+            Self::U32(m) => m.render_mesh_range_with_program(prg, range),
+        }
+    }
+}
@@ -4,7 +4,9 @@ use gl_wrapper::util::aggregator_obj::*;
 use gl_wrapper::util::buffer_obj::*;
 use gl_wrapper::HasGLEnum;
 use glam::*;
+use std::ops::Range;
 
+use crate::layout::VertexLayout;
 use crate::mesh::UnboundMesh;
 
 pub trait World2D {
@@ -33,8 +35,24 @@ pub trait Model {
     ) -> Result<(), ()>
     where
         AT: HasGLEnum;
+    /// Binds every attribute of an interleaved VBO at once, see `BoundMesh::adapt_mesh_to_layout`, Err(()) for models that don't support it
+    fn adapt_model_to_layout<AT>(
+        self: &mut Self,
+        _attrib: &VBO<AT>,
+        _layout: &VertexLayout,
+        _p: &Program,
+    ) -> Result<(), ()>
+    where
+        AT: HasGLEnum,
+    {
+        Err(())
+    }
     fn adapt_model_to_program(self: &mut Self, p: &Program) -> Result<(), ()>;
     fn render(self: &Self, _prg: &Program) -> Result<(), ()>;
+    /// Renders only the indices in range, e.g. one material range, Err(()) for models that don't support it
+    fn render_range(self: &Self, _prg: &Program, _range: Range<usize>) -> Result<(), ()> {
+        Err(())
+    }
 }
 
 pub struct UnboundModel2D<'a, IT>
@@ -209,6 +227,19 @@ where
             .adapt_mesh_to_attrib::<AT>(attrib, attrib_loc)
     }
 
+    fn adapt_model_to_layout<AT>(
+        self: &mut Self,
+        attrib: &VBO<AT>,
+        layout: &VertexLayout,
+        p: &Program,
+    ) -> Result<(), ()>
+    where
+        AT: HasGLEnum,
+    {
+        self.mesh
+            .adapt_mesh_to_layout::<AT>(attrib, layout, p)
+    }
+
     #[inline(always)]
     fn adapt_model_to_program(self: &mut Self, p: &Program) -> Result<(), ()> {
         self.mesh.adapt_mesh_to_program(p)
@@ -219,42 +250,29 @@ where
         self.mesh.render_mesh_with_program(_prg)?;
         Ok(())
     }
+
+    #[inline(always)]
+    fn render_range(self: &Self, _prg: &Program, range: Range<usize>) -> Result<(), ()> {
+        self.mesh.render_mesh_range_with_program(_prg, range)
+    }
 }
 
 
-pub struct UnboundModel3D<'a, IT>
-where
-    IT: HasGLEnum
-{
+/// Position, size and the cached matrix they make, shared by the 3D models
+struct Transform3D {
     pos: Vec3,
     aabb: Vec3,
     mat: Option<Mat4>,
-    mesh: crate::mesh::UnboundMesh<'a, IT>
 }
 
-
-impl<'a, IT> UnboundModel3D<'a, IT>
-where
-    IT: HasGLEnum{
-        pub fn new(m: UnboundMesh<'a, IT>) -> Self{
-            Self{
-                pos: Vec3::ZERO,
-                aabb: Vec3::ONE,
-                mat: None,
-                mesh: m
-            }
-        }
-
-        pub fn bind<'b>(&'b mut self, bn1: &'b mut VAOBouncer, bn2: &'b mut IBOBouncer) -> BoundModel3D<'b, IT>{
-            BoundModel3D{
-                pos: &mut self.pos,
-                aabb: &mut self.aabb,
-                mat: &mut self.mat,
-                mesh: self.mesh.bind(bn1, bn2)
-            }
+impl Transform3D {
+    fn new() -> Self{
+        Self{
+            pos: Vec3::ZERO,
+            aabb: Vec3::ONE,
+            mat: None,
         }
-
-
+    }
 
     #[inline]
     fn update_mat(self: &mut Self) {
@@ -266,27 +284,19 @@ where
     }
 
     #[inline]
-    pub fn set_size(self: &mut Self, val: Vec3) {
+    fn set_size(self: &mut Self, val: Vec3) {
         self.aabb = val;
         self.mat = None;
     }
 
     #[inline]
-    pub fn scale(self: &mut Self, val: Vec3) {
+    fn scale(self: &mut Self, val: Vec3) {
         self.aabb *= val;
         self.mat = None;
     }
-
-    #[inline]
-    pub fn get_size(self: &Self) -> &Vec3 {
-        self.get_aabb()
-    }
 }
 
-
-
-impl<IT> World3D for UnboundModel3D<'_, IT>
-where IT: HasGLEnum{
+impl World3D for Transform3D {
     #[inline]
     fn get_mat(self: &mut Self) -> &Mat4 {
         if self.mat.is_none() {
@@ -315,106 +325,368 @@ where IT: HasGLEnum{
     }
 }
 
+/// Implements `World3D` and the size methods of a 3D model by forwarding them to its `transform`
+macro_rules! forward_transform_3d {
+    ($model:ty) => {
+        impl<IT> $model
+        where IT: HasGLEnum{
+            #[inline]
+            pub fn set_size(self: &mut Self, val: Vec3) {
+                self.transform.set_size(val);
+            }
+
+            #[inline]
+            pub fn scale(self: &mut Self, val: Vec3) {
+                self.transform.scale(val);
+            }
+
+            #[inline]
+            pub fn get_size(self: &Self) -> &Vec3 {
+                self.get_aabb()
+            }
+        }
+
+        impl<IT> World3D for $model
+        where IT: HasGLEnum{
+            #[inline]
+            fn get_mat(self: &mut Self) -> &Mat4 {
+                self.transform.get_mat()
+            }
+
+            #[inline]
+            fn get_aabb(self: &Self) -> &Vec3 {
+                self.transform.get_aabb()
+            }
+            #[inline]
+            fn get_pos(self: &Self) -> &Vec3 {
+                self.transform.get_pos()
+            }
+            #[inline]
+            fn set_pos(self: &mut Self, val: Vec3) {
+                self.transform.set_pos(val);
+            }
+            #[inline]
+            fn strafe(self: &mut Self, val: Vec3) {
+                self.transform.strafe(val);
+            }
+        }
+    };
+}
+
+pub struct UnboundModel3D<'a, IT>
+where
+    IT: HasGLEnum
+{
+    transform: Transform3D,
+    mesh: crate::mesh::UnboundMesh<'a, IT>
+}
+
+
+impl<'a, IT> UnboundModel3D<'a, IT>
+where
+    IT: HasGLEnum{
+        pub fn new(m: UnboundMesh<'a, IT>) -> Self{
+            Self{
+                transform: Transform3D::new(),
+                mesh: m
+            }
+        }
+
+        pub fn bind<'b>(&'b mut self, bn1: &'b mut VAOBouncer, bn2: &'b mut IBOBouncer) -> BoundModel3D<'b, IT>{
+            BoundModel3D{
+                transform: &mut self.transform,
+                mesh: self.mesh.bind(bn1, bn2)
+            }
+        }
+}
+
+forward_transform_3d!(UnboundModel3D<'_, IT>);
+
 pub struct BoundModel3D<'b, IT>
 where
     IT: HasGLEnum,
 {
-    pos: &'b mut Vec3,
-    aabb: &'b mut Vec3,
-    mat: &'b mut Option<Mat4>,
+    transform: &'b mut Transform3D,
     mesh: crate::mesh::BoundMesh<'b, IT>,
 }
 
+forward_transform_3d!(BoundModel3D<'_, IT>);
 
-impl<'a, 'b, IT> BoundModel3D<'a, IT>
+impl<'a, 'b, IT> Model for BoundModel3D<'a, IT>
 where
     IT: HasGLEnum,
 {
- 
+    // Make sure there is no overhead in passing variables
+    #[inline(always)]
+    fn adapt_model_to_attrib<AT>(
+        self: &mut Self,
+        attrib: &VBO<AT>,
+        attrib_loc: GLuint,
+    ) -> Result<(), ()>
+    where
+        AT: HasGLEnum,
+    {
+        self.mesh.adapt_mesh_to_attrib(attrib, attrib_loc)
+    }
 
-    #[inline]
-    fn update_mat(self: &mut Self) {
-        *self.mat = Some(Mat4::from_scale_rotation_translation(
-            *self.aabb,
-            Quat::IDENTITY,
-            *self.pos,
-        ));
+    fn adapt_model_to_layout<AT>(
+        self: &mut Self,
+        attrib: &VBO<AT>,
+        layout: &VertexLayout,
+        p: &Program,
+    ) -> Result<(), ()>
+    where
+        AT: HasGLEnum,
+    {
+        self.mesh.adapt_mesh_to_layout(attrib, layout, p)
     }
 
-    #[inline]
-    pub fn set_size(self: &mut Self, val: Vec3) {
-        *self.aabb = val;
-        *self.mat = None;
+    fn adapt_model_to_program(self: &mut Self, p: &Program) -> Result<(), ()> {
+        self.mesh.adapt_mesh_to_program(p)
     }
 
     #[inline]
-    pub fn scale(self: &mut Self, val: Vec3) {
-        *self.aabb *= val;
-        *self.mat = None;
+    fn render(self: &Self, _prg: &Program) -> Result<(), ()> {
+        self.mesh.render_mesh_with_program(_prg)?;
+        Ok(())
     }
 
     #[inline]
-    pub fn get_size(self: &Self) -> &Vec3 {
-        self.get_aabb()
+    fn render_range(self: &Self, _prg: &Program, range: Range<usize>) -> Result<(), ()> {
+        self.mesh.render_mesh_range_with_program(_prg, range)
     }
 }
 
-impl<'a, 'b, IT> World3D for BoundModel3D<'a, IT>
+/// What `UnboundLodModel3D::select_level` compares the `LodLevel::switch` values to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodMetric {
+    /// Distance from the camera to the model's position, the levels switch at increasing distances
+    Distance,
+    /// Fraction of the screen height the bounding sphere covers, fov_y is the vertical field of view in radians
+    ScreenSize { fov_y: f32 },
+}
+
+/// The level for far ( bigger is less detail ), only changing once far is past a switch by more than hysteresis
+fn pick_level(mut current: usize, count: usize, far: f32, hysteresis: f32, switch: impl Fn(usize) -> f32) -> usize {
+    while current + 1 < count && far > switch(current + 1) * (1.0 + hysteresis) {
+        current += 1;
+    }
+    while current > 0 && far < switch(current) * (1.0 - hysteresis) {
+        current -= 1;
+    }
+    current
+}
+
+/// One mesh of an `UnboundLodModel3D`
+pub struct LodLevel<'a, IT>
 where
-    IT: HasGLEnum,
+    IT: HasGLEnum
 {
-    #[inline]
-    fn get_mat(self: &mut Self) -> &Mat4 {
-        if self.mat.is_none() {
-            self.update_mat();
+    pub mesh: UnboundMesh<'a, IT>,
+    /// Where this level starts being used, in the units of the `LodMetric` ( ignored for the first level, use 0.0 )
+    pub switch: f32,
+}
+
+/// Why `UnboundLodModel3D::new` refused its levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LodError {
+    NoLevels,
+    /// This level's switch isn't past the one before it ( or isn't positive for `LodMetric::ScreenSize` )
+    SwitchOutOfOrder(usize),
+}
+
+impl std::fmt::Display for LodError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LodError::NoLevels => write!(f, "a LOD model needs at least one level"),
+            LodError::SwitchOutOfOrder(l) => write!(f, "the switch of LOD level {} doesn't come after the one before it", l),
         }
-        self.mat.as_ref().unwrap()
     }
+}
 
-    #[inline]
-    fn get_aabb(self: &Self) -> &Vec3 {
-        &self.aabb
+impl std::error::Error for LodError {}
+
+/// Bigger means less detail for both metrics
+fn farness(metric: LodMetric, value: f32) -> f32 {
+    match metric {
+        LodMetric::Distance => value,
+        LodMetric::ScreenSize{ .. } => 1.0 / value,
+    }
+}
+
+/// Checks that switches ( without the first one's ) get farther with every level
+fn check_switches(metric: LodMetric, switches: &[f32]) -> Result<(), LodError> {
+    if switches.is_empty() {
+        return Err(LodError::NoLevels);
+    }
+    let mut last = f32::NEG_INFINITY;
+    for (l, switch) in switches.iter().enumerate().skip(1) {
+        let far = farness(metric, *switch);
+        let valid = match metric {
+            LodMetric::Distance => true,
+            LodMetric::ScreenSize{ .. } => *switch > 0.0,
+        };
+        if !valid || far.is_nan() || far <= last {
+            return Err(LodError::SwitchOutOfOrder(l));
+        }
+        last = far;
+    }
+    Ok(())
+}
+
+/// Like `UnboundModel3D` but with meshes of decreasing detail, `select_level` picks the one to render
+pub struct UnboundLodModel3D<'a, IT>
+where
+    IT: HasGLEnum
+{
+    transform: Transform3D,
+    levels: Vec<LodLevel<'a, IT>>,
+    metric: LodMetric,
+    /// Of the meshes' bounding sphere, before `set_size`
+    center: Vec3,
+    radius: f32,
+    /// Fraction a level's switch has to be passed by before the level changes
+    hysteresis: f32,
+    current: usize,
+}
+
+impl<'a, IT> UnboundLodModel3D<'a, IT>
+where
+    IT: HasGLEnum{
+    /// levels go from the most detailed to the least, with switches that increase for `LodMetric::Distance` and decrease for `LodMetric::ScreenSize`
+    pub fn new(levels: Vec<LodLevel<'a, IT>>, metric: LodMetric) -> Result<Self, LodError>{
+        check_switches(metric, &levels.iter().map(|l| l.switch).collect::<Vec<f32>>())?;
+        Ok(Self{
+            transform: Transform3D::new(),
+            levels,
+            metric,
+            center: Vec3::ZERO,
+            radius: 1.0,
+            hysteresis: 0.1,
+            current: 0,
+        })
+    }
+
+    /// The meshes' bounding sphere ( see `ObjData::bounding_sphere` ) that distances are measured to, the origin and 1.0 unless set
+    pub fn with_bounding_sphere(mut self, center: Vec3, radius: f32) -> Self{
+        self.center = center;
+        self.radius = radius;
+        self
+    }
+
+    /// How far ( as a fraction ) past a switch the metric has to go to change the level, 0.1 unless set
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self{
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    /// Picks the level for how the model is seen from camera ( e.g. a `Camera3D` ) and returns it, call it every frame before `bind`
+    pub fn select_level<C>(self: &mut Self, camera: &C) -> usize
+    where C: World3D{
+        let (pos, aabb) = (*self.get_pos(), *self.get_aabb());
+        let distance = (pos + self.center * aabb).distance(*camera.get_pos());
+        let metric = self.metric;
+        let far = match metric {
+            LodMetric::Distance => distance,
+            LodMetric::ScreenSize{ fov_y } => {
+                let radius = self.radius * aabb.abs().max_element();
+                if distance <= radius {
+                    0.0
+                } else {
+                    // The sphere's silhouette is a cone with half angle asin( radius / distance )
+                    let size = radius / (distance * distance - radius * radius).sqrt() / (fov_y / 2.0).tan();
+                    farness(metric, size)
+                }
+            }
+        };
+        let levels = &self.levels;
+        self.current = pick_level(self.current, levels.len(), far, self.hysteresis, |l| farness(metric, levels[l].switch));
+        self.current
     }
+
     #[inline]
-    fn get_pos(self: &Self) -> &Vec3 {
-        &self.pos
+    pub fn get_level(self: &Self) -> usize {
+        self.current
     }
+
+    /// Uses level until the next `select_level`, the last level if it's past that
     #[inline]
-    fn set_pos(self: &mut Self, val: Vec3) {
-        *self.pos = val;
-        *self.mat = None;
+    pub fn set_level(self: &mut Self, level: usize) {
+        self.current = level.min(self.levels.len() - 1);
     }
+
     #[inline]
-    fn strafe(self: &mut Self, val: Vec3) {
-        *self.pos += val;
-        *self.mat = None;
+    pub fn level_count(self: &Self) -> usize {
+        self.levels.len()
     }
-}
 
-impl<'a, 'b, IT> Model for BoundModel3D<'a, IT>
-where
-    IT: HasGLEnum,
-{
-    // Make sure there is no overhead in passing variables
-    #[inline(always)]
-    fn adapt_model_to_attrib<AT>(
-        self: &mut Self,
-        attrib: &VBO<AT>,
-        attrib_loc: GLuint,
-    ) -> Result<(), ()>
-    where
-        AT: HasGLEnum,
-    {
-        self.mesh.adapt_mesh_to_attrib(attrib, attrib_loc)
+    /// Binds the level picked by `select_level`, see `bind_level`
+    pub fn bind<'b>(&'b mut self, bn1: &'b mut VAOBouncer, bn2: &'b mut IBOBouncer) -> Option<BoundModel3D<'b, IT>>{
+        let level = self.current;
+        self.bind_level(level, bn1, bn2)
     }
 
-    fn adapt_model_to_program(self: &mut Self, p: &Program) -> Result<(), ()> {
-        self.mesh.adapt_mesh_to_program(p)
+    /// Binds any of the levels ( each has its own VAO to adapt ), None if there's no such level
+    pub fn bind_level<'b>(&'b mut self, level: usize, bn1: &'b mut VAOBouncer, bn2: &'b mut IBOBouncer) -> Option<BoundModel3D<'b, IT>>{
+        let l = self.levels.get_mut(level)?;
+        Some(BoundModel3D{
+            transform: &mut self.transform,
+            mesh: l.mesh.bind(bn1, bn2)
+        })
     }
+}
 
-    #[inline]
-    fn render(self: &Self, _prg: &Program) -> Result<(), ()> {
-        self.mesh.render_mesh_with_program(_prg)?;
-        Ok(())
+forward_transform_3d!(UnboundLodModel3D<'_, IT>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_doesnt_flicker_at_a_switch() {
+        let switches = [0.0, 10.0, 20.0];
+        let pick = |current, far| pick_level(current, switches.len(), far, 0.1, |l| switches[l]);
+        // Sitting exactly at a switch keeps whichever level is in use
+        for current in 0..2 {
+            let mut level = current;
+            for _ in 0..10 {
+                level = pick(level, 10.0);
+                assert_eq!(level, current);
+            }
+        }
+        assert_eq!(pick(0, 10.9), 0);
+        assert_eq!(pick(0, 11.1), 1);
+        assert_eq!(pick(1, 9.1), 1);
+        assert_eq!(pick(1, 8.9), 0);
+        assert_eq!(pick(0, 100.0), 2);
+        assert_eq!(pick(2, 0.0), 0);
+    }
+
+    #[test]
+    fn lod_switches_must_get_farther() {
+        let screen = LodMetric::ScreenSize{ fov_y: 1.0 };
+        assert_eq!(check_switches(LodMetric::Distance, &[]), Err(LodError::NoLevels));
+        assert_eq!(check_switches(LodMetric::Distance, &[0.0]), Ok(()));
+        assert_eq!(check_switches(LodMetric::Distance, &[0.0, 10.0, 20.0]), Ok(()));
+        assert_eq!(check_switches(LodMetric::Distance, &[0.0, 20.0, 10.0]), Err(LodError::SwitchOutOfOrder(2)));
+        assert_eq!(check_switches(LodMetric::Distance, &[0.0, 10.0, 10.0]), Err(LodError::SwitchOutOfOrder(2)));
+        assert_eq!(check_switches(LodMetric::Distance, &[0.0, f32::NAN]), Err(LodError::SwitchOutOfOrder(1)));
+        // Smaller on screen is farther
+        assert_eq!(check_switches(screen, &[0.0, 0.5, 0.1]), Ok(()));
+        assert_eq!(check_switches(screen, &[0.0, 0.1, 0.5]), Err(LodError::SwitchOutOfOrder(2)));
+        assert_eq!(check_switches(screen, &[0.0, 0.5, 0.0]), Err(LodError::SwitchOutOfOrder(2)));
+    }
+
+    #[test]
+    fn transform_caches_its_matrix() {
+        let mut t = Transform3D::new();
+        assert_eq!(*t.get_mat(), Mat4::IDENTITY);
+        t.set_pos(Vec3::new(1.0, 2.0, 3.0));
+        t.scale(Vec3::splat(2.0));
+        t.strafe(Vec3::X);
+        assert_eq!(t.get_mat().transform_point3(Vec3::ONE), Vec3::new(4.0, 4.0, 5.0));
+        t.set_size(Vec3::ONE);
+        assert_eq!(t.get_mat().transform_point3(Vec3::ZERO), Vec3::new(2.0, 2.0, 3.0));
+        assert_eq!(*t.get_aabb(), Vec3::ONE);
     }
 }
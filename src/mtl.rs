@@ -0,0 +1,167 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::obj::{ObjError, ObjErrorKind, ParseMode};
+
+/// A material from a .mtl file, texture paths are already joined with the directory of the .mtl file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// Ka
+    pub ambient: [f32; 3],
+    /// Kd
+    pub diffuse: [f32; 3],
+    /// Ks
+    pub specular: [f32; 3],
+    /// Ns
+    pub shininess: f32,
+    /// d ( or 1 - Tr )
+    pub dissolve: f32,
+    pub illum: u32,
+    pub ambient_map: Option<PathBuf>,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub shininess_map: Option<PathBuf>,
+    pub dissolve_map: Option<PathBuf>,
+    /// map_bump or bump
+    pub bump_map: Option<PathBuf>,
+}
+
+impl Material {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ambient: [0.2, 0.2, 0.2],
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [1.0, 1.0, 1.0],
+            shininess: 0.0,
+            dissolve: 1.0,
+            illum: 2,
+            ambient_map: None,
+            diffuse_map: None,
+            specular_map: None,
+            shininess_map: None,
+            dissolve_map: None,
+            bump_map: None,
+        }
+    }
+}
+
+fn parse_num<N: FromStr>(s: &str) -> Result<N, ObjErrorKind> {
+    s.parse().map_err(|_| ObjErrorKind::InvalidNumber(s.to_owned()))
+}
+
+fn parse_color(args: &[&str]) -> Result<[f32; 3], ObjErrorKind> {
+    match args {
+        // A single value means grey
+        [v] => { let v = parse_num(v)?; Ok([v, v, v]) },
+        [r, g, b] => Ok([parse_num(r)?, parse_num(g)?, parse_num(b)?]),
+        _ => Err(ObjErrorKind::WrongArgumentCount(args.len()))
+    }
+}
+
+fn parse_scalar<N: FromStr>(args: &[&str]) -> Result<N, ObjErrorKind> {
+    match args {
+        [v] => parse_num(v),
+        _ => Err(ObjErrorKind::WrongArgumentCount(args.len()))
+    }
+}
+
+/// Skips the options ( -s 1 1 1, -clamp on, ... ) in front of the texture file name
+fn parse_map(args: &[&str], base_dir: &Path) -> Result<PathBuf, ObjErrorKind> {
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') {
+        i += match args[i] {
+            "-mm" => 3,
+            // Up to 3 numbers follow these
+            "-o" | "-s" | "-t" => 1 + args[i + 1..].iter().take(3).take_while(|a| a.parse::<f32>().is_ok()).count(),
+            _ => 2,
+        };
+    }
+    if i >= args.len() {
+        return Err(ObjErrorKind::WrongArgumentCount(args.len()));
+    }
+    Ok(base_dir.join(args[i..].join(" ")))
+}
+
+/// Parses a .mtl file, texture paths are resolved relative to base_dir
+pub fn parse_mtl<R: BufRead>(r: R, base_dir: &Path, mode: ParseMode) -> Result<(Vec<Material>, Vec<ObjError>), ObjError> {
+    let mut materials: Vec<Material> = Vec::new();
+    let mut warnings: Vec<ObjError> = Vec::new();
+
+    for (line_ind, full_line) in r.lines().enumerate() {
+        let line_no = line_ind + 1;
+        let full_line = full_line.map_err(|e| ObjError::new(line_no, "", ObjErrorKind::Io(e.kind())))?;
+        let line = full_line.split('#').next().unwrap_or("").trim();
+        let mut parts = line.split_whitespace();
+        let keyword = match parts.next() {
+            Some(k) => k,
+            None => continue
+        };
+        let args: Vec<&str> = parts.collect();
+
+        let res = if keyword == "newmtl" {
+            materials.push(Material::new(&args.join(" ")));
+            Ok(())
+        } else if let Some(m) = materials.last_mut() {
+            match keyword {
+                "Ka" => parse_color(&args).map(|c| m.ambient = c),
+                "Kd" => parse_color(&args).map(|c| m.diffuse = c),
+                "Ks" => parse_color(&args).map(|c| m.specular = c),
+                "Ns" => parse_scalar(&args).map(|v| m.shininess = v),
+                "d" => parse_scalar(&args).map(|v| m.dissolve = v),
+                "Tr" => parse_scalar(&args).map(|v: f32| m.dissolve = 1.0 - v),
+                "illum" => parse_scalar(&args).map(|v| m.illum = v),
+                "map_Ka" => parse_map(&args, base_dir).map(|p| m.ambient_map = Some(p)),
+                "map_Kd" => parse_map(&args, base_dir).map(|p| m.diffuse_map = Some(p)),
+                "map_Ks" => parse_map(&args, base_dir).map(|p| m.specular_map = Some(p)),
+                "map_Ns" => parse_map(&args, base_dir).map(|p| m.shininess_map = Some(p)),
+                "map_d" => parse_map(&args, base_dir).map(|p| m.dissolve_map = Some(p)),
+                "map_bump" | "map_Bump" | "bump" => parse_map(&args, base_dir).map(|p| m.bump_map = Some(p)),
+                // Valid statements that we don't use
+                "Ke" | "Ni" | "Tf" | "sharpness" | "disp" | "decal" | "refl" | "map_refl" | "map_Ke"
+                | "Pr" | "Pm" | "Ps" | "Pc" | "Pcr" | "aniso" | "anisor" | "norm" | "map_Pr" | "map_Pm" | "map_Ps" => Ok(()),
+                _ => Err(ObjErrorKind::UnknownStatement(keyword.to_owned()))
+            }
+        } else {
+            Err(ObjErrorKind::NoMaterialDefined)
+        };
+
+        if let Err(kind) = res {
+            mode.report(ObjError::new(line_no, &full_line, kind), &mut warnings)?;
+        }
+    }
+    Ok((materials, warnings))
+}
+
+/// Writes a path relative to base_dir if it is inside of it
+fn write_map<W: Write>(w: &mut W, keyword: &str, map: &Option<PathBuf>, base_dir: &Path) -> io::Result<()> {
+    match map {
+        Some(p) => writeln!(w, "{} {}", keyword, p.strip_prefix(base_dir).unwrap_or(p).display()),
+        None => Ok(())
+    }
+}
+
+/// Writes materials in the .mtl format, parse_mtl with the same base_dir gives them back
+pub fn write_mtl<W: Write>(mut w: W, materials: &[Material], base_dir: &Path) -> io::Result<()> {
+    for m in materials {
+        writeln!(w, "newmtl {}", m.name)?;
+        writeln!(w, "Ka {} {} {}", m.ambient[0], m.ambient[1], m.ambient[2])?;
+        writeln!(w, "Kd {} {} {}", m.diffuse[0], m.diffuse[1], m.diffuse[2])?;
+        writeln!(w, "Ks {} {} {}", m.specular[0], m.specular[1], m.specular[2])?;
+        writeln!(w, "Ns {}", m.shininess)?;
+        writeln!(w, "d {}", m.dissolve)?;
+        writeln!(w, "illum {}", m.illum)?;
+        write_map(&mut w, "map_Ka", &m.ambient_map, base_dir)?;
+        write_map(&mut w, "map_Kd", &m.diffuse_map, base_dir)?;
+        write_map(&mut w, "map_Ks", &m.specular_map, base_dir)?;
+        write_map(&mut w, "map_Ns", &m.shininess_map, base_dir)?;
+        write_map(&mut w, "map_d", &m.dissolve_map, base_dir)?;
+        write_map(&mut w, "map_bump", &m.bump_map, base_dir)?;
+        writeln!(w)?;
+    }
+    w.flush()
+}
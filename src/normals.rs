@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use crate::vector::{cross, dot, normalize, sub};
+
+/// How the normals of faces are combined at a shared vertex
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// Bigger faces count more
+    Area,
+    /// Faces count by the angle of their corner at the vertex
+    Angle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalGeneration {
+    /// Every face gets its own normal
+    Flat,
+    /// Faces in the same smoothing group share normals at the vertices they have in common
+    Smooth(NormalWeighting),
+    /// Like smooth, but two faces only share a normal if the angle between them is at most max_angle ( in degrees )
+    Crease { weighting: NormalWeighting, max_angle: f32 },
+}
+
+/// A normal for every corner of triangles, flat in smoothing group 0 and shared by position index in the others
+pub fn generate_normals(positions: &[[f64; 3]], triangles: &[[usize; 3]], groups: &[u32], mode: NormalGeneration) -> Vec<[f64; 3]> {
+    // Twice the area times the unit normal
+    let face: Vec<[f64; 3]> = triangles.iter().map(|t| {
+        let p = [positions[t[0]], positions[t[1]], positions[t[2]]];
+        cross(sub(p[1], p[0]), sub(p[2], p[0]))
+    }).collect();
+    let unit: Vec<[f64; 3]> = face.iter().map(|n| normalize(*n).unwrap_or([0.0, 0.0, 0.0])).collect();
+
+    let mut out: Vec<[f64; 3]> = unit.iter().flat_map(|n| std::iter::repeat_n(*n, 3)).collect();
+    let (weighting, min_cos) = match mode {
+        NormalGeneration::Flat => return out,
+        NormalGeneration::Smooth(w) => (w, None),
+        NormalGeneration::Crease { weighting, max_angle } => (weighting, Some((max_angle as f64).to_radians().cos())),
+    };
+
+    let weighted = |t: usize, k: usize| -> [f64; 3] {
+        match weighting {
+            NormalWeighting::Area => face[t],
+            NormalWeighting::Angle => {
+                let tri = triangles[t];
+                let p = positions[tri[k]];
+                let a = normalize(sub(positions[tri[(k + 1) % 3]], p));
+                let b = normalize(sub(positions[tri[(k + 2) % 3]], p));
+                let angle = match (a, b) {
+                    (Some(a), Some(b)) => dot(a, b).clamp(-1.0, 1.0).acos(),
+                    _ => 0.0
+                };
+                [unit[t][0] * angle, unit[t][1] * angle, unit[t][2] * angle]
+            }
+        }
+    };
+
+    // Corners ( triangle, corner ) that may share a normal
+    let mut shared: HashMap<(usize, u32), Vec<(usize, usize)>> = HashMap::new();
+    for (t, tri) in triangles.iter().enumerate() {
+        if groups[t] == 0 {
+            continue;
+        }
+        for (k, v) in tri.iter().enumerate() {
+            shared.entry((*v, groups[t])).or_default().push((t, k));
+        }
+    }
+
+    for corners in shared.values() {
+        match min_cos {
+            None => {
+                let mut sum = [0.0; 3];
+                for (t, k) in corners {
+                    let w = weighted(*t, *k);
+                    sum = [sum[0] + w[0], sum[1] + w[1], sum[2] + w[2]];
+                }
+                if let Some(n) = normalize(sum) {
+                    for (t, k) in corners {
+                        out[t * 3 + k] = n;
+                    }
+                }
+            },
+            Some(min_cos) => {
+                for (t, k) in corners {
+                    let mut sum = [0.0; 3];
+                    for (other_t, other_k) in corners {
+                        if dot(unit[*t], unit[*other_t]) >= min_cos {
+                            let w = weighted(*other_t, *other_k);
+                            sum = [sum[0] + w[0], sum[1] + w[1], sum[2] + w[2]];
+                        }
+                    }
+                    if let Some(n) = normalize(sum) {
+                        out[t * 3 + k] = n;
+                    }
+                }
+            }
+        }
+    }
+    out
+}
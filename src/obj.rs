@@ -1,20 +1,49 @@
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, hash_map::RandomState},
     fs::File,
-    io::{self, Read},
-    str::FromStr, borrow::Borrow,
-    hash::Hash, convert::TryFrom
+    io::{self, BufRead, Write},
+    str::FromStr, fmt,
+    hash::BuildHasher, convert::{TryFrom, TryInto},
+    ops::Range, path::Path
 };
 
-use objld::LineResult;
+use glam::{Vec2, Vec3, Vec4};
 use rayon::prelude::*;
 
+use crate::mtl::{self, Material};
+use crate::normals::{self, NormalGeneration};
+use crate::tangents;
+use crate::triangulate::{self, TriangulationError};
 
-#[derive(PartialEq, Eq, Clone, Copy)]
-pub enum Dimension{OneDim = 1, TwoDim = 2, ThreeDim = 3}
 
+/// Vertex data types that can be converted to f64, e.g. for triangulation and welding
+/// ( scalars no longer need `Hash + Eq`, custom ones like float wrappers have to implement this instead )
+pub trait ObjScalar: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(v: f64) -> Self;
+}
+
+impl ObjScalar for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 { self as f64 }
+    #[inline]
+    fn from_f64(v: f64) -> Self { v as f32 }
+}
+
+impl ObjScalar for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 { self }
+    #[inline]
+    fn from_f64(v: f64) -> Self { v }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Dimension{OneDim = 1, TwoDim = 2, ThreeDim = 3, FourDim = 4}
+
+#[derive(PartialEq, Clone, Debug)]
 pub struct VertexAttribs<T> {
-    data: Vec<T>,
+    pub(crate) data: Vec<T>,
     elem_per_vert: Dimension
 }
 
@@ -49,21 +78,33 @@ where T: Copy + Default {
         self.data.push(element.2);  
     }
 
+    pub fn push4d(&mut self, element: (T, T, T, T)){
+        assert!(self.elem_per_vert == Dimension::FourDim);
+        self.data.push(element.0);
+        self.data.push(element.1);
+        self.data.push(element.2);
+        self.data.push(element.3);
+    }
+
     pub fn get_elem_per_vert(&self) -> Dimension { self.elem_per_vert }
 
+    /// Allocates, use `get_slice` or `as_arrays` in loops
     pub fn get(&self, ind: usize) -> Vec<T>{
         match self.elem_per_vert{
             Dimension::OneDim => vec![self.data[ind]],
             Dimension::TwoDim => vec![self.data[ind*2], self.data[ind*2+1]],
             Dimension::ThreeDim => vec![self.data[ind*3], self.data[ind*3+1], self.data[ind*3+2]],
+            Dimension::FourDim => vec![self.data[ind*4], self.data[ind*4+1], self.data[ind*4+2], self.data[ind*4+3]],
         }
     }
 
+    /// Takes a Vec, use `set_slice` in loops
     pub fn set(&mut self, ind: usize, val: Vec<T>){
         match self.elem_per_vert{
             Dimension::OneDim => { self.data[ind] = val[0]; },
             Dimension::TwoDim => { self.data[ind*2] = val[0]; self.data[ind*2+1] = val[1]; },
             Dimension::ThreeDim => {self.data[ind*3] = val[0]; self.data[ind*3+1] = val[1]; self.data[ind*3+2] = val[2];}
+            Dimension::FourDim => {self.data[ind*4] = val[0]; self.data[ind*4+1] = val[1]; self.data[ind*4+2] = val[2]; self.data[ind*4+3] = val[3];}
         }
     }
 
@@ -71,7 +112,8 @@ where T: Copy + Default {
         match self.elem_per_vert{
             Dimension::OneDim => self.data.resize(len, T::default()),
             Dimension::TwoDim => self.data.resize(len*2, T::default()),
-            Dimension::ThreeDim => self.data.resize(len*3, T::default())
+            Dimension::ThreeDim => self.data.resize(len*3, T::default()),
+            Dimension::FourDim => self.data.resize(len*4, T::default())
         }
     }
 
@@ -80,151 +122,2050 @@ where T: Copy + Default {
             Dimension::OneDim => self.data.len(),
             Dimension::TwoDim => self.data.len()/2,
             Dimension::ThreeDim => self.data.len()/3,
+            Dimension::FourDim => self.data.len()/4,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.data.is_empty()
+    }
+
+    /// The components of one element, without allocating
+    #[inline]
+    pub fn get_slice(&self, ind: usize) -> &[T]{
+        let dim = self.elem_per_vert as usize;
+        &self.data[ind*dim..(ind+1)*dim]
+    }
+
+    #[inline]
+    pub fn get_slice_mut(&mut self, ind: usize) -> &mut [T]{
+        let dim = self.elem_per_vert as usize;
+        &mut self.data[ind*dim..(ind+1)*dim]
+    }
+
+    /// Panics if val doesn't have one value per component
+    #[inline]
+    pub fn set_slice(&mut self, ind: usize, val: &[T]){
+        self.get_slice_mut(ind).copy_from_slice(val);
+    }
+
+    /// Every element as a slice
+    pub fn iter(&self) -> std::slice::ChunksExact<'_, T>{
+        self.data.chunks_exact(self.elem_per_vert as usize)
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::ChunksExactMut<'_, T>{
+        let dim = self.elem_per_vert as usize;
+        self.data.chunks_exact_mut(dim)
+    }
+
+    /// Every element as an array, None if N isn't the dimension or doesn't divide data.len()
+    pub fn as_arrays<const N: usize>(&self) -> Option<&[[T; N]]>
+    where T: bytemuck::Pod {
+        if N != self.elem_per_vert as usize {
+            return None;
+        }
+        bytemuck::try_cast_slice(&self.data).ok()
+    }
+
+    pub fn as_arrays_mut<const N: usize>(&mut self) -> Option<&mut [[T; N]]>
+    where T: bytemuck::Pod {
+        if N != self.elem_per_vert as usize {
+            return None;
         }
+        bytemuck::try_cast_slice_mut(&mut self.data).ok()
     }
 }
 
+impl<T> VertexAttribs<T>
+where T: ObjScalar + Default {
+    /// Element ind as a glam vector, missing components are 0 and extra ones are ignored
+    pub fn get_vec2(&self, ind: usize) -> Vec2{
+        let e = self.get_slice(ind);
+        Vec2::new(e[0].to_f64() as f32, e.get(1).map_or(0.0, |v| v.to_f64() as f32))
+    }
+
+    pub fn get_vec3(&self, ind: usize) -> Vec3{
+        let e = self.get_slice(ind);
+        let c = |k: usize| e.get(k).map_or(0.0, |v| v.to_f64() as f32);
+        Vec3::new(c(0), c(1), c(2))
+    }
+
+    pub fn get_vec4(&self, ind: usize) -> Vec4{
+        let e = self.get_slice(ind);
+        let c = |k: usize| e.get(k).map_or(0.0, |v| v.to_f64() as f32);
+        Vec4::new(c(0), c(1), c(2), c(3))
+    }
+
+    pub fn iter_vec2(&self) -> impl Iterator<Item = Vec2> + '_{
+        (0..self.len()).map(move |i| self.get_vec2(i))
+    }
+
+    pub fn iter_vec3(&self) -> impl Iterator<Item = Vec3> + '_{
+        (0..self.len()).map(move |i| self.get_vec3(i))
+    }
+
+    pub fn iter_vec4(&self) -> impl Iterator<Item = Vec4> + '_{
+        (0..self.len()).map(move |i| self.get_vec4(i))
+    }
+
+    /// Sets the first components of element ind, as many as it has
+    pub fn set_vec3(&mut self, ind: usize, val: Vec3){
+        for (c, v) in self.get_slice_mut(ind).iter_mut().zip(val.to_array().iter()) {
+            *c = T::from_f64(*v as f64);
+        }
+    }
+}
+
+/// What went wrong while loading an obj file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjErrorKind {
+    /// Reading the file failed
+    Io(io::ErrorKind),
+    /// The line starts with a keyword we don't know about
+    UnknownStatement(String),
+    /// A value couldn't be parsed as a number
+    InvalidNumber(String),
+    /// A vertex data line had fewer components than the dimension that was requested for it, or more that aren't optional
+    DimensionMismatch { expected: Dimension, found: usize },
+    /// A face, line or point index was zero or not an integer
+    InvalidIndex(String),
+    /// A face, line or point index pointed past the last element of attribute loaded so far, or before the first one
+    IndexOutOfRange { attribute: &'static str, index: String, loaded: usize },
+    /// A face had less than 3 vertices, a line less than 2 or a point none
+    TooFewVertices(usize),
+    /// A face has no area, e.g. all of its vertices are on one line
+    DegeneratePolygon,
+    /// Two edges of a face cross each other
+    SelfIntersectingPolygon,
+    /// There are more unique vertices than the index type can address
+    IndicesTooBig,
+    /// A statement had the wrong amount of arguments
+    WrongArgumentCount(usize),
+    /// A .mtl statement came before any newmtl
+    NoMaterialDefined,
+    /// A library named by mtllib couldn't be opened
+    MissingMaterialLibrary(String),
+    /// A usemtl named a material that no loaded library defines
+    UnknownMaterial(String),
+    /// An operation needs an attribute ( with a specific dimension ) that wasn't loaded
+    MissingAttribute(&'static str),
+    /// An attribute doesn't have one element per position
+    AttributeCountMismatch { attribute: &'static str, expected: usize, found: usize },
+    /// The header of a ( PLY ) file is missing or broken
+    InvalidHeader(String),
+    /// A binary file ends too early
+    UnexpectedEof,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjError {
+    /// 1-based line number, 0 if the error isn't tied to a specific line
+    pub line: usize,
+    /// The offending line without the trailing newline, or e.g. the file or the element and row for errors without one
+    pub text: String,
+    pub kind: ObjErrorKind,
+}
+
+impl ObjError {
+    pub(crate) fn new(line: usize, text: &str, kind: ObjErrorKind) -> Self {
+        Self{ line, text: text.to_owned(), kind }
+    }
+}
+
+impl fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjErrorKind::Io(k) => write!(f, "io error: {:?}", k),
+            ObjErrorKind::UnknownStatement(s) => write!(f, "unknown statement '{}'", s),
+            ObjErrorKind::InvalidNumber(s) => write!(f, "'{}' is not a valid number", s),
+            ObjErrorKind::DimensionMismatch { expected, found } => write!(f, "expected {} components but found {}", *expected as usize, found),
+            ObjErrorKind::InvalidIndex(s) => write!(f, "'{}' is not a valid index", s),
+            ObjErrorKind::IndexOutOfRange { attribute, index, loaded } => write!(f, "{} index '{}' is out of range ({} loaded so far)", attribute, index, loaded),
+            ObjErrorKind::TooFewVertices(n) => write!(f, "a face needs at least 3 vertices, a line 2 and a point 1, but this one has {}", n),
+            ObjErrorKind::DegeneratePolygon => write!(f, "degenerate face"),
+            ObjErrorKind::SelfIntersectingPolygon => write!(f, "self-intersecting face"),
+            ObjErrorKind::IndicesTooBig => write!(f, "too many unique vertices for the index type"),
+            ObjErrorKind::WrongArgumentCount(n) => write!(f, "wrong amount of arguments ({})", n),
+            ObjErrorKind::NoMaterialDefined => write!(f, "statement before any newmtl"),
+            ObjErrorKind::MissingMaterialLibrary(s) => write!(f, "couldn't open material library '{}'", s),
+            ObjErrorKind::UnknownMaterial(s) => write!(f, "material '{}' isn't defined in any material library", s),
+            ObjErrorKind::MissingAttribute(s) => write!(f, "missing {}", s),
+            ObjErrorKind::AttributeCountMismatch { attribute, expected, found } => write!(f, "there are {} {} but {} positions", found, attribute, expected),
+            ObjErrorKind::InvalidHeader(s) => write!(f, "invalid header: {}", s),
+            ObjErrorKind::UnexpectedEof => write!(f, "unexpected end of file"),
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 && self.text.is_empty() {
+            write!(f, "{}", self.kind)
+        } else if self.line == 0 {
+            write!(f, "{}: {}", self.text, self.kind)
+        } else {
+            write!(f, "line {}: {} (in '{}')", self.line, self.kind, self.text)
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<ObjError> for io::Error {
+    fn from(e: ObjError) -> Self {
+        let kind = match e.kind {
+            ObjErrorKind::Io(k) => k,
+            _ => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, e)
+    }
+}
+
+/// How often ( in lines ) the progress callback is called while loading
+pub const PROGRESS_INTERVAL: usize = 100_000;
+
+/// How far loading has got, passed to the progress callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub bytes_read: u64,
+    /// None if the size of the input isn't known
+    pub total_bytes: Option<u64>,
+    pub lines_read: usize,
+}
+
+/// How to deal with bad lines while loading
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ParseMode {
+    /// Fail on the first error
+    Strict,
+    /// Skip bad lines and faces, and return them as warnings together with the rest of the mesh
+    Lenient,
+}
+
+impl ParseMode {
+    /// Err(e) in strict mode, otherwise e is added to warnings
+    pub(crate) fn report(self, e: ObjError, warnings: &mut Vec<ObjError>) -> Result<(), ObjError> {
+        match self {
+            ParseMode::Strict => Err(e),
+            ParseMode::Lenient => { warnings.push(e); Ok(()) }
+        }
+    }
+}
+
+/// A part of `ObjData::indicies` that is drawn with one material
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaterialRange {
+    /// The name given to usemtl
+    pub name: String,
+    /// Index into `ObjData::materials`, None until the material libraries are loaded or if none of them defines the material
+    pub material: Option<usize>,
+    pub range: Range<usize>,
+}
+
+/// A part of `ObjData::indicies` with the same object, group and material, all sub-meshes share the vertex data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubMesh {
+    /// The name given to the last o statement, empty if there was none
+    pub object: String,
+    /// The name(s) given to the last g statement, empty if there was none since the last o
+    pub group: String,
+    /// The name given to the last usemtl
+    pub material_name: Option<String>,
+    /// Index into `ObjData::materials`, None until the material libraries are loaded
+    pub material: Option<usize>,
+    pub range: Range<usize>,
+}
+
+impl SubMesh {
+    /// "object/group", or just whichever of the two is set
+    pub fn name(&self) -> String {
+        match (self.object.is_empty(), self.group.is_empty()) {
+            (false, false) => format!("{}/{}", self.object, self.group),
+            (true, _) => self.group.clone(),
+            (false, true) => self.object.clone(),
+        }
+    }
+}
+
+/// How close every component of two values must be for them to become one vertex, None for exact matches only
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WeldTolerance {
+    pub pos: Option<f64>,
+    pub tex: Option<f64>,
+    pub norm: Option<f64>,
+    pub color: Option<f64>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
 pub struct ObjData<T, I> {
     pub pos_data: VertexAttribs<T>,
     pub tex_data: Option<VertexAttribs<T>>,
     pub norm_data: Option<VertexAttribs<T>>,
+    /// Tangent ( xyz ) and handedness ( w ) per vertex, only there after calling `generate_tangents`
+    pub tangent_data: Option<VertexAttribs<T>>,
+    /// RGB or RGBA per vertex, for obj files set it to an empty `VertexAttribs` to read `v x y z r g b` colours
+    pub color_data: Option<VertexAttribs<T>>,
     pub indicies: Vec<I>,
+    /// Line segments from l statements, two indices each ( for GL_LINES ). A polyline with n vertices becomes n - 1 segments.
+    pub line_indicies: Vec<I>,
+    /// Points from p statements, one index each ( for GL_POINTS ). Lines and points use the same vertices as the triangles.
+    pub point_indicies: Vec<I>,
+    /// File names given to mtllib, relative to the obj file
+    pub mtl_libs: Vec<String>,
+    pub materials: Vec<Material>,
+    pub material_ranges: Vec<MaterialRange>,
+    /// Covers every index that was loaded, in order
+    pub sub_meshes: Vec<SubMesh>,
+    /// Generates normals for faces without them while loading, shared within smoothing groups
+    pub normal_generation: Option<NormalGeneration>,
+    /// Corners whose attributes are within these tolerances become one vertex while loading, the first corner's values are kept
+    pub weld: WeldTolerance,
+}
+
+/// Indices in the smallest type that fits them, see `ObjData::compact_indicies` and `mesh::AnyIBO`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexBuffer {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl IndexBuffer {
+    /// Picks the type from the biggest index, None if some index doesn't fit in a u32
+    pub fn from_indicies<I: Copy + TryInto<usize>>(indicies: &[I]) -> Option<Self> {
+        let mut max: usize = 0;
+        for i in indicies {
+            max = max.max((*i).try_into().ok()?);
+        }
+        // Every index was checked above
+        let to_usize = |i: &I| (*i).try_into().unwrap_or(0);
+        if max <= u8::MAX as usize {
+            Some(Self::U8(indicies.iter().map(|i| to_usize(i) as u8).collect()))
+        } else if max <= u16::MAX as usize {
+            Some(Self::U16(indicies.iter().map(|i| to_usize(i) as u16).collect()))
+        } else if max <= u32::MAX as usize {
+            Some(Self::U32(indicies.iter().map(|i| to_usize(i) as u32).collect()))
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U8(v) => v.len(),
+            Self::U16(v) => v.len(),
+            Self::U32(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, ind: usize) -> usize {
+        match self {
+            Self::U8(v) => v[ind] as usize,
+            Self::U16(v) => v[ind] as usize,
+            Self::U32(v) => v[ind] as usize,
+        }
+    }
+
+    /// Bytes per index
+    pub fn index_size(&self) -> usize {
+        match self {
+            Self::U8(_) => 1,
+            Self::U16(_) => 2,
+            Self::U32(_) => 4,
+        }
+    }
 }
 
+/// What makes two corners the same vertex without tolerances, values padded to 4 components by `weld_key`
 #[derive(Eq, PartialEq, Hash)]
-struct Vertex<T>{
-    pub pos: Vec<T>,
-    pub norm: Option<Vec<T>>,
-    pub tex: Option<Vec<T>>
+struct Vertex{
+    pub pos: [u64; 4],
+    pub norm: Option<[u64; 4]>,
+    pub tex: Option<[u64; 4]>,
+    pub color: Option<[u64; 4]>
+}
+
+/// Key of a value for exact deduplication, its bits with -0 and 0 the same
+pub(crate) fn weld_key(v: f64) -> u64 {
+    (v + 0.0).to_bits()
+}
+
+/// Whether every component of a is within tolerance of b, or the same ( see `weld_key` ) if there is none
+pub(crate) fn within(a: &[f64], b: &[f64], tolerance: Option<f64>) -> bool {
+    a.iter().zip(b).all(|(a, b)| match tolerance {
+        Some(t) if t > 0.0 => (a - b).abs() <= t,
+        _ => weld_key(*a) == weld_key(*b)
+    })
+}
+
+/// The vertices welded so far by position, on a grid as big as the tolerance
+pub(crate) struct WeldGrid {
+    tolerance: Option<f64>,
+    cells: HashMap<[u64; 4], Vec<usize>>,
+}
+
+impl WeldGrid {
+    pub(crate) fn new(tolerance: Option<f64>) -> Self {
+        Self{ tolerance: tolerance.filter(|t| *t > 0.0), cells: HashMap::new() }
+    }
+
+    fn cell(&self, pos: &[f64]) -> [u64; 4] {
+        let mut cell = [0; 4];
+        for (c, v) in cell.iter_mut().zip(pos) {
+            *c = match self.tolerance {
+                Some(t) => (v / t).floor() as i64 as u64,
+                None => weld_key(*v)
+            };
+        }
+        cell
+    }
+
+    /// The first vertex near pos that same accepts, a position within tolerance can be one cell over so the neighbouring cells are searched too
+    pub(crate) fn find(&self, pos: &[f64], same: impl Fn(usize) -> bool) -> Option<usize> {
+        let center = self.cell(pos);
+        let dims = pos.len().min(4) as u32;
+        let neighbours = if self.tolerance.is_some() { 3usize.pow(dims) } else { 1 };
+        let mut first: Option<usize> = None;
+        for n in 0..neighbours {
+            let mut cell = center;
+            let mut rest = n;
+            for c in cell.iter_mut().take(dims as usize).filter(|_| neighbours > 1) {
+                *c = c.wrapping_add((rest % 3) as u64).wrapping_sub(1);
+                rest /= 3;
+            }
+            // Vertices are added in order, so the first match of a cell is its lowest
+            if let Some(id) = self.cells.get(&cell).and_then(|ids| ids.iter().copied().find(|id| same(*id))) {
+                first = Some(first.map_or(id, |f| f.min(id)));
+            }
+        }
+        first
+    }
+
+    pub(crate) fn insert(&mut self, pos: &[f64], id: usize) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push(id);
+    }
+}
+
+/// The vertex data corners point into, for making their `Vertex` keys and comparing them within tolerance
+struct Welder<'a, T> {
+    pos: &'a VertexAttribs<T>,
+    tex: Option<&'a VertexAttribs<T>>,
+    norm: Option<&'a VertexAttribs<T>>,
+    color: Option<&'a VertexAttribs<T>>,
+    tolerance: WeldTolerance,
+}
+
+impl<'a, T> Welder<'a, T>
+where T: ObjScalar + Default {
+    fn key(&self, c: &Corner) -> Vertex {
+        let keys = |attribs: &VertexAttribs<T>, i: usize| -> [u64; 4] {
+            let mut k = [0; 4];
+            for (k, v) in k.iter_mut().zip(attribs.get_slice(i)) {
+                *k = weld_key(v.to_f64());
+            }
+            k
+        };
+        Vertex{
+            pos: keys(self.pos, c.pos),
+            norm: self.norm.zip(c.norm).map(|(n, i)| keys(n, i)),
+            tex: self.tex.zip(c.tex).map(|(t, i)| keys(t, i)),
+            // Colours belong to positions
+            color: self.color.map(|col| keys(col, c.pos)),
+        }
+    }
+
+    /// Values of element i padded to 4, and how many there are
+    fn values(attribs: &VertexAttribs<T>, i: usize) -> ([f64; 4], usize) {
+        let mut v = [0.0; 4];
+        let s = attribs.get_slice(i);
+        for (v, s) in v.iter_mut().zip(s) {
+            *v = s.to_f64();
+        }
+        (v, s.len())
+    }
+
+    fn pos_of(&self, c: &Corner) -> ([f64; 4], usize) {
+        Self::values(self.pos, c.pos)
+    }
+
+    /// Whether a and b have the same attributes within tolerance, like equal `key`s without tolerances
+    fn same(&self, a: &Corner, b: &Corner) -> bool {
+        let close = |attribs: Option<&VertexAttribs<T>>, i: Option<usize>, j: Option<usize>, tolerance: Option<f64>| match (attribs, i, j) {
+            (None, _, _) => true,
+            (Some(attribs), Some(i), Some(j)) => {
+                let ((a, len), (b, _)) = (Self::values(attribs, i), Self::values(attribs, j));
+                within(&a[..len], &b[..len], tolerance)
+            },
+            (Some(_), i, j) => i.is_none() && j.is_none()
+        };
+        close(Some(self.pos), Some(a.pos), Some(b.pos), self.tolerance.pos)
+            && close(self.tex, a.tex, b.tex, self.tolerance.tex)
+            && close(self.norm, a.norm, b.norm, self.tolerance.norm)
+            && close(self.color, Some(a.pos), Some(b.pos), self.tolerance.color)
+    }
+}
+
+/// One corner of a face, with 0-based indices into the loaded vertex data
+#[derive(Clone, Copy)]
+struct Corner {
+    pos: usize,
+    tex: Option<usize>,
+    norm: Option<usize>,
+}
+
+/// Pushes a `v`, `vt` or `vn` value ( a missing w is 1 ), padded or truncated on error so later indices stay right
+fn push_vertex_data<T>(attribs: &mut VertexAttribs<T>, args: &[&str]) -> Result<(), ObjErrorKind>
+where T: FromStr + ObjScalar + Default {
+    let dim = attribs.get_elem_per_vert();
+    if dim == Dimension::FourDim && args.len() == 3 {
+        let res = push_vertex_data(attribs, &[args[0], args[1], args[2], "0"]);
+        if let Some(w) = attribs.data.last_mut() { *w = T::from_f64(1.0); }
+        return res;
+    }
+    let mut err = None;
+    let mut vals: Vec<T> = Vec::with_capacity(dim as usize);
+    for a in args.iter().take(dim as usize) {
+        match a.parse::<T>() {
+            Ok(v) => vals.push(v),
+            Err(_) => {
+                if err.is_none() { err = Some(ObjErrorKind::InvalidNumber((*a).to_owned())); }
+                vals.push(T::default());
+            }
+        }
+    }
+    if err.is_none() && args.len() != dim as usize {
+        err = Some(ObjErrorKind::DimensionMismatch { expected: dim, found: args.len() });
+    }
+    vals.resize(dim as usize, T::default());
+    attribs.data.extend_from_slice(&vals);
+    match err {
+        Some(e) => Err(e),
+        None => Ok(())
+    }
+}
+
+/// 0-based index of an obj index ( 1-based, or negative to count back ), only loaded elements may be used
+fn parse_index(s: &str, loaded: usize, attribute: &'static str) -> Result<usize, ObjErrorKind> {
+    let ind: isize = s.parse().map_err(|_| ObjErrorKind::InvalidIndex(s.to_owned()))?;
+    let out_of_range = || ObjErrorKind::IndexOutOfRange{ attribute, index: s.to_owned(), loaded };
+    match ind {
+        0 => Err(ObjErrorKind::InvalidIndex(s.to_owned())),
+        _ if ind.unsigned_abs() > loaded => Err(out_of_range()),
+        _ if ind > 0 => Ok(ind as usize - 1),
+        _ => Ok(loaded - ind.unsigned_abs())
+    }
+}
+
+/// Smoothing group state while loading
+#[derive(Default)]
+struct Smoothing {
+    /// The group set by the last s statement, 0 is off
+    current: u32,
+    /// Whether the file had any s statements
+    seen: bool,
+    /// The group of every triangle loaded so far
+    tri_groups: Vec<u32>,
+}
+
+/// The counts are how many elements were loaded before this line, None if the attribute wasn't requested
+#[derive(Clone, Copy)]
+struct LoadedCounts {
+    pos: usize,
+    tex: Option<usize>,
+    norm: Option<usize>,
+}
+
+/// args without the optional components that don't fit dim, the w of a `v` and the v and w of a `vt`
+fn drop_optional<'a, 'b>(keyword: &str, args: &'b [&'a str], dim: Dimension) -> &'b [&'a str] {
+    let dim = dim as usize;
+    match keyword {
+        "v" if dim == 3 && args.len() == 4 => &args[..3],
+        "vt" if args.len() > dim && args.len() <= 3 => &args[..dim],
+        _ => args
+    }
+}
+
+/// Parses v, v/vt, v//vn or v/vt/vn, every index has to point at data loaded before the line
+fn parse_corner(s: &str, counts: LoadedCounts) -> Result<Corner, ObjErrorKind> {
+    let mut parts = s.split('/');
+    let pos_ind = parse_index(parts.next().unwrap_or(""), counts.pos, "position")?;
+    let tex_ind = match (parts.next(), counts.tex) {
+        (Some(t), Some(tex_count)) if !t.is_empty() => Some(parse_index(t, tex_count, "texture coordinate")?),
+        _ => None
+    };
+    let norm_ind = match (parts.next(), counts.norm) {
+        (Some(n), Some(norm_count)) if !n.is_empty() => Some(parse_index(n, norm_count, "normal")?),
+        _ => None
+    };
+    Ok(Corner{ pos: pos_ind, tex: tex_ind, norm: norm_ind })
+}
+
+/// Splits a line into its keyword and arguments, None for empty lines and comments
+fn split_line(full_line: &str) -> Option<(&str, Vec<&str>)> {
+    let line = full_line.split('#').next().unwrap_or("").trim();
+    let mut parts = line.split_whitespace();
+    let keyword = parts.next()?;
+    Some((keyword, parts.collect()))
+}
+
+/// Valid statements that we don't use
+fn is_ignored_statement(keyword: &str) -> bool {
+    matches!(keyword, "vp" | "mg" | "usemap" | "maplib"
+        | "cstype" | "deg" | "bmat" | "step" | "curv" | "curv2" | "surf" | "parm" | "trim" | "hole"
+        | "scrv" | "sp" | "end" | "con" | "bevel" | "c_interp" | "d_interp" | "lod" | "shadow_obj" | "trace_obj"
+        | "ctech" | "stech")
+}
+
+/// Handles v, vt and vn lines, skipping attributes that weren't requested and reading `v x y z r g b` colours
+fn push_vertex_line<T>(keyword: &str, args: &[&str], pos: &mut VertexAttribs<T>, tex: &mut Option<VertexAttribs<T>>, norm: &mut Option<VertexAttribs<T>>, color: &mut Option<VertexAttribs<T>>) -> Result<(), ObjErrorKind>
+where T: FromStr + ObjScalar + Default {
+    match (keyword, tex, norm) {
+        ("v", _, _) => {
+            let dim = pos.get_elem_per_vert() as usize;
+            // x y z r g b also works for 4D positions, w is 1 then
+            let pos_len = match args.len() {
+                n if n == dim + 3 => dim,
+                6 if dim == 4 => 3,
+                n => n
+            };
+            let res = push_vertex_data(pos, drop_optional("v", &args[..pos_len], pos.get_elem_per_vert()));
+            if let Some(c) = color {
+                let rgb = &args[pos_len..];
+                let res_color = match (c.get_elem_per_vert(), rgb.len()) {
+                    (Dimension::ThreeDim, 3) => push_vertex_data(c, rgb),
+                    (Dimension::FourDim, 3) => push_vertex_data(c, &[rgb[0], rgb[1], rgb[2], "1"]),
+                    _ => {
+                        let len = c.data.len() + c.get_elem_per_vert() as usize;
+                        c.data.resize(len, T::from_f64(1.0));
+                        Ok(())
+                    }
+                };
+                res.and(res_color)
+            } else {
+                res
+            }
+        },
+        ("vt", Some(t), _) => {
+            let dim = t.get_elem_per_vert();
+            push_vertex_data(t, drop_optional("vt", args, dim))
+        },
+        ("vn", _, Some(n)) => push_vertex_data(n, args),
+        _ => Ok(())
+    }
+}
+
+/// Corners of the l and p statements loaded so far, they don't take part in normal generation, sub-meshes or material ranges
+#[derive(Default)]
+struct LinesAndPoints {
+    /// Two corners per segment
+    lines: Vec<Corner>,
+    points: Vec<Corner>,
+}
+
+/// Parses an l line into segments or a p line into points
+fn parse_line_element(keyword: &str, args: &[&str], counts: LoadedCounts, out: &mut LinesAndPoints) -> Result<(), ObjErrorKind> {
+    let corners: Vec<Corner> = args.iter().map(|c| parse_corner(c, counts)).collect::<Result<_, _>>()?;
+    match keyword {
+        "l" if corners.len() < 2 => Err(ObjErrorKind::TooFewVertices(corners.len())),
+        "l" => {
+            for s in corners.windows(2) {
+                out.lines.extend_from_slice(s);
+            }
+            Ok(())
+        },
+        _ if corners.is_empty() => Err(ObjErrorKind::TooFewVertices(0)),
+        _ => {
+            out.points.extend(corners);
+            Ok(())
+        }
+    }
+}
+
+/// Parses and triangulates an f line, pos must contain at least the first counts.pos positions
+fn parse_face<T>(args: &[&str], counts: LoadedCounts, pos: &VertexAttribs<T>, out: &mut Vec<Corner>) -> Result<(), ObjErrorKind>
+where T: ObjScalar + Default {
+    let corners: Vec<Corner> = args.iter().map(|c| parse_corner(c, counts)).collect::<Result<_, _>>()?;
+    triangulate_face(pos, &corners, out)
+}
+
+/// Splits a face into triangles, its corners must be checked already ( see `parse_corner` )
+fn triangulate_face<T>(pos: &VertexAttribs<T>, corners: &[Corner], out: &mut Vec<Corner>) -> Result<(), ObjErrorKind>
+where T: ObjScalar + Default {
+    if corners.len() < 3 {
+        return Err(ObjErrorKind::TooFewVertices(corners.len()));
+    }
+    // There is no geometry to check with 1D positions, just make a fan
+    if pos.get_elem_per_vert() == Dimension::OneDim {
+        for i in 1..corners.len() - 1 {
+            out.push(corners[0]); out.push(corners[i]); out.push(corners[i + 1]);
+        }
+        return Ok(());
+    }
+    let mut points: Vec<[f64; 3]> = Vec::with_capacity(corners.len());
+    for c in corners {
+        let p = pos.get_slice(c.pos);
+        points.push([p[0].to_f64(), p[1].to_f64(), p.get(2).map_or(0.0, |z| z.to_f64())]);
+    }
+    let tris = triangulate::triangulate(&points).map_err(|e| match e {
+        TriangulationError::Degenerate => ObjErrorKind::DegeneratePolygon,
+        TriangulationError::SelfIntersecting => ObjErrorKind::SelfIntersectingPolygon,
+    })?;
+    for t in tris {
+        out.push(corners[t[0]]); out.push(corners[t[1]]); out.push(corners[t[2]]);
+    }
+    Ok(())
 }
 
 impl<T, I> ObjData<T, I>
-where T: FromStr + Send + Copy+ PartialEq + Hash + Eq + Default, I: FromStr + Copy + Send + TryFrom<usize>{
+where T: FromStr + Send + ObjScalar + Default, I: FromStr + Copy + Send + TryFrom<usize>{
     pub fn new(pos_data_dim: Dimension, tex_data_dim: Option<Dimension>, norm_data_dim: Option<Dimension>) -> Self{
         Self{
             pos_data: VertexAttribs::new(pos_data_dim),
             tex_data: if let Some(d) = tex_data_dim { Some(VertexAttribs::new(d)) } else {None},
             norm_data: if let Some(d) = norm_data_dim { Some(VertexAttribs::new(d))} else {None},
-            indicies: Vec::new()
+            tangent_data: None,
+            color_data: None,
+            indicies: Vec::new(),
+            line_indicies: Vec::new(),
+            point_indicies: Vec::new(),
+            mtl_libs: Vec::new(),
+            materials: Vec::new(),
+            material_ranges: Vec::new(),
+            sub_meshes: Vec::new(),
+            normal_generation: None,
+            weld: WeldTolerance::default()
         }
     }
 
+    /// Loads the file in lenient mode, skipping bad lines, use `load_with_mode` for strict mode or to get the warnings
     pub fn load(&mut self, f: &mut File) -> io::Result<()>{
-        let parsed: Vec<LineResult<T, isize>> = {
-            let mut lines = String::new();
-            f.read_to_string(&mut lines)?;
-            let r = objld::parse_file(lines.borrow()).collect();
-            drop(lines);
-            r
-        };
-
-        let mut vert_ind: Vec<objld::VertexIndeces<isize>> = Vec::new();
-        for line in parsed{
-            match line{
-                    LineResult::FaceLine(f) => {
-                            match f{
-                                objld::Face::Face3 { v1, v2, v3 } => { 
-                                    vert_ind.push(v1); vert_ind.push(v2); vert_ind.push(v3); 
-                                },
-                                objld::Face::Face4 { v1, v2, v3, v4 } => {
-                                    vert_ind.push(v1); vert_ind.push(v2); vert_ind.push(v3); // First triangle of square 
-                                    vert_ind.push(v3); vert_ind.push(v4); vert_ind.push(v1); // Second triangle of square
-                                }
-                            }
-                    },
-                    LineResult::VertDataLine(v) => {
-                            match v{
-                                objld::VertexData::Coord2 { x, y } if self.pos_data.get_elem_per_vert() == Dimension::TwoDim => self.pos_data.push2d((x, y)),
-                                objld::VertexData::Coord3 { x, y, z } if self.pos_data.get_elem_per_vert() == Dimension::ThreeDim => self.pos_data.push3d((x, y, z)),
-                                objld::VertexData::Normal { x, y, z } => {
-                                    if let Some(n) = &mut self.norm_data{
-                                        if n.get_elem_per_vert() == Dimension::ThreeDim {
-                                            n.push3d((x, y, z));
-                                        }
-                                    }
-                                },
-                                objld::VertexData::TextureCoord3 { u, v, w } => {
-                                    if let Some(t) = &mut self.tex_data{
-                                        if t.get_elem_per_vert() == Dimension::ThreeDim {
-                                            t.push3d((u, v, w));
-                                        }
-                                    }
-                                },
-                                objld::VertexData::TextureCoord2 { u, v } => {
-                                    if let Some(t) = &mut self.tex_data{
-                                        if t.get_elem_per_vert() == Dimension::TwoDim {
-                                            t.push2d((u, v));
-                                        }
-                                    } 
-                                },
-                                objld::VertexData::TextureCoord1 { u } => {
-                                    if let Some(t) = &mut self.tex_data{
-                                        if t.get_elem_per_vert() == Dimension::OneDim {
-                                            t.push1d(u);
-                                        }
-                                    } 
-                                },
-                                _ => {}
-                            }
-                    },
-                    LineResult::Error(_e) =>{ }, // FIXME: Should find a way to not avoid errors
-                    LineResult::NoData => {}
-            }
-        }
-
-        let mut h: HashMap<Vertex<T>, usize> = HashMap::new();
-        let mut curr_ind: usize = 0;
-
-        for v in vert_ind {
-            let process_index = |ind: isize, data_len: usize| -> usize{
-                if ind < 0 { (data_len as isize + ind) as usize } else {ind as usize}
+        self.load_with_mode(f, ParseMode::Lenient).map(|_| ()).map_err(io::Error::from)
+    }
+
+    /// Loads the file, in lenient mode the returned vector contains every line that was skipped
+    pub fn load_with_mode(&mut self, f: &mut File, mode: ParseMode) -> Result<Vec<ObjError>, ObjError>{
+        let total = f.metadata().ok().map(|m| m.len());
+        self.load_stream(io::BufReader::new(f), total, mode, None)
+    }
+
+    /// Loads the file at path, see `load_from_reader`
+    pub fn load_from_path(&mut self, path: &Path, mode: ParseMode, progress: Option<&mut dyn FnMut(LoadProgress)>) -> Result<Vec<ObjError>, ObjError>{
+        let f = File::open(path).map_err(|e| ObjError::new(0, &path.to_string_lossy(), ObjErrorKind::Io(e.kind())))?;
+        let total = f.metadata().ok().map(|m| m.len());
+        self.load_stream(io::BufReader::new(f), total, mode, progress)
+    }
+
+    /// Loads obj data that is already in memory, e.g. from include_bytes!
+    pub fn load_from_bytes(&mut self, bytes: &[u8], mode: ParseMode, progress: Option<&mut dyn FnMut(LoadProgress)>) -> Result<Vec<ObjError>, ObjError>{
+        self.load_stream(bytes, Some(bytes.len() as u64), mode, progress)
+    }
+
+    /// Loads obj data line by line, calling progress every PROGRESS_INTERVAL lines and at the end
+    pub fn load_from_reader<R: BufRead>(&mut self, r: R, mode: ParseMode, progress: Option<&mut dyn FnMut(LoadProgress)>) -> Result<Vec<ObjError>, ObjError>{
+        self.load_stream(r, None, mode, progress)
+    }
+
+    fn load_stream<R: BufRead>(&mut self, mut r: R, total_bytes: Option<u64>, mode: ParseMode, mut progress: Option<&mut dyn FnMut(LoadProgress)>) -> Result<Vec<ObjError>, ObjError>{
+        let mut warnings: Vec<ObjError> = Vec::new();
+        let mut report = |e: ObjError| mode.report(e, &mut warnings);
+
+        let mut vert_ind: Vec<Corner> = Vec::new();
+        let mut elements = LinesAndPoints::default();
+        let mut smoothing = Smoothing::default();
+        // Faces before any o, g or usemtl go into an unnamed sub-mesh
+        let start = self.indicies.len();
+        self.begin_sub_mesh(start, |m| { m.object.clear(); m.group.clear(); m.material_name = None; });
+
+        let mut buf: Vec<u8> = Vec::new();
+        let mut bytes_read: u64 = 0;
+        let mut line_no: usize = 0;
+        loop {
+            buf.clear();
+            let n = r.read_until(b'\n', &mut buf).map_err(|e| ObjError::new(line_no + 1, "", ObjErrorKind::Io(e.kind())))?;
+            if n == 0 {
+                break;
+            }
+            bytes_read += n as u64;
+            line_no += 1;
+            // Don't fail on non UTF-8 comments or names
+            let full_line = String::from_utf8_lossy(&buf);
+            let full_line = full_line.trim_end_matches(['\n', '\r']);
+            if let Err(kind) = self.parse_line(full_line, &mut vert_ind, &mut elements, &mut smoothing) {
+                report(ObjError::new(line_no, full_line, kind))?;
+            }
+            if line_no.is_multiple_of(PROGRESS_INTERVAL) {
+                if let Some(p) = progress.as_mut() {
+                    p(LoadProgress{ bytes_read, total_bytes, lines_read: line_no });
+                }
+            }
+        }
+        if let Some(p) = progress.as_mut() {
+            p(LoadProgress{ bytes_read, total_bytes, lines_read: line_no });
+        }
+
+        self.finish_ranges(self.indicies.len() + vert_ind.len());
+        self.generate_missing_normals(&mut vert_ind, &smoothing);
+        let (vert_ind, tri_end, line_count) = self.append_lines_and_points(vert_ind, elements);
+        self.build_indicies(vert_ind)?;
+        self.split_off_lines_and_points(tri_end, line_count);
+        Ok(warnings)
+    }
+
+    fn loaded_counts(&self) -> LoadedCounts{
+        LoadedCounts{
+            pos: self.pos_data.len(),
+            tex: self.tex_data.as_ref().map(|t| t.len()),
+            norm: self.norm_data.as_ref().map(|n| n.len())
+        }
+    }
+
+    fn parse_line(&mut self, full_line: &str, vert_ind: &mut Vec<Corner>, elements: &mut LinesAndPoints, smoothing: &mut Smoothing) -> Result<(), ObjErrorKind>{
+        let (keyword, args) = match split_line(full_line) {
+            Some(l) => l,
+            None => return Ok(())
+        };
+        match keyword {
+            "v" | "vt" | "vn" => push_vertex_line(keyword, &args, &mut self.pos_data, &mut self.tex_data, &mut self.norm_data, &mut self.color_data),
+            "f" => {
+                parse_face(&args, self.loaded_counts(), &self.pos_data, vert_ind)?;
+                smoothing.tri_groups.resize(vert_ind.len() / 3, smoothing.current);
+                Ok(())
+            },
+            "l" | "p" => parse_line_element(keyword, &args, self.loaded_counts(), elements),
+            _ => self.apply_statement(keyword, &args, self.indicies.len() + vert_ind.len(), smoothing)
+        }
+    }
+
+    /// Handles every statement other than vertex data and faces, start is the index the next face will begin at
+    fn apply_statement(&mut self, keyword: &str, args: &[&str], start: usize, smoothing: &mut Smoothing) -> Result<(), ObjErrorKind>{
+        match keyword {
+            "s" => {
+                smoothing.seen = true;
+                smoothing.current = match args {
+                    ["off"] => 0,
+                    [group] => group.parse().map_err(|_| ObjErrorKind::InvalidNumber((*group).to_owned()))?,
+                    _ => return Err(ObjErrorKind::WrongArgumentCount(args.len()))
+                };
+                Ok(())
+            },
+            "mtllib" => {
+                if args.is_empty() {
+                    Err(ObjErrorKind::WrongArgumentCount(0))
+                } else {
+                    self.mtl_libs.extend(args.iter().map(|a| (*a).to_owned()));
+                    Ok(())
+                }
+            },
+            "o" => {
+                let name = args.join(" ");
+                self.begin_sub_mesh(start, |m| { m.object = name; m.group.clear(); });
+                Ok(())
+            },
+            "g" => {
+                let name = args.join(" ");
+                self.begin_sub_mesh(start, |m| m.group = name);
+                Ok(())
+            },
+            "usemtl" => {
+                let name = args.join(" ");
+                self.begin_sub_mesh(start, |m| m.material_name = Some(name.clone()));
+                if let Some(last) = self.material_ranges.last_mut() {
+                    last.range.end = start;
+                }
+                // Nothing was drawn with the last material, so it doesn't need a range
+                if self.material_ranges.last().is_some_and(|r| r.range.is_empty()) {
+                    self.material_ranges.pop();
+                }
+                match self.material_ranges.last() {
+                    // Continue the last range if it has the same material
+                    Some(last) if last.name == name => {},
+                    _ => self.material_ranges.push(MaterialRange{ name, material: None, range: start..start })
+                }
+                Ok(())
+            },
+            _ if is_ignored_statement(keyword) => Ok(()),
+            _ => Err(ObjErrorKind::UnknownStatement(keyword.to_owned()))
+        }
+    }
+
+    /// Closes the last material range and sub-mesh at end
+    fn finish_ranges(&mut self, end: usize){
+        if let Some(last) = self.material_ranges.last_mut() {
+            last.range.end = end;
+        }
+        if self.material_ranges.last().is_some_and(|r| r.range.is_empty()) {
+            self.material_ranges.pop();
+        }
+        if let Some(last) = self.sub_meshes.last_mut() {
+            last.range.end = end;
+        }
+        if self.sub_meshes.last().is_some_and(|m| m.range.is_empty()) {
+            self.sub_meshes.pop();
+        }
+    }
+
+    /// Gives every corner without a normal a generated one, if normal_generation is set
+    fn generate_missing_normals(&mut self, vert_ind: &mut [Corner], smoothing: &Smoothing){
+        let mode = match self.normal_generation {
+            Some(m) => m,
+            None => return
+        };
+        let norms = match &mut self.norm_data {
+            Some(n) if n.get_elem_per_vert() == Dimension::ThreeDim => n,
+            _ => return
+        };
+        if self.pos_data.get_elem_per_vert() == Dimension::OneDim || vert_ind.iter().all(|c| c.norm.is_some()) {
+            return;
+        }
+        let pos_data = &self.pos_data;
+        let positions: Vec<[f64; 3]> = pos_data.iter().map(|p| {
+            [p[0].to_f64(), p[1].to_f64(), p.get(2).map_or(0.0, |z| z.to_f64())]
+        }).collect();
+        let triangles: Vec<[usize; 3]> = vert_ind.chunks(3).map(|t| [t[0].pos, t[1].pos, t[2].pos]).collect();
+        let groups: Vec<u32> = if smoothing.seen { smoothing.tri_groups.clone() } else { vec![1; triangles.len()] };
+
+        let generated = normals::generate_normals(&positions, &triangles, &groups, mode);
+        for (c, n) in vert_ind.iter_mut().zip(generated) {
+            if c.norm.is_none() {
+                norms.data.extend_from_slice(&[T::from_f64(n[0]), T::from_f64(n[1]), T::from_f64(n[2])]);
+                c.norm = Some(norms.len() - 1);
+            }
+        }
+    }
+
+    /// Appends line and point corners to deduplicate them with the triangles, returns where the triangles end and the line corner count too
+    fn append_lines_and_points(&self, mut vert_ind: Vec<Corner>, elements: LinesAndPoints) -> (Vec<Corner>, usize, usize){
+        let tri_end = self.indicies.len() + vert_ind.len();
+        let line_count = elements.lines.len();
+        vert_ind.extend(elements.lines);
+        vert_ind.extend(elements.points);
+        (vert_ind, tri_end, line_count)
+    }
+
+    /// Moves the indices that `append_lines_and_points` added from `indicies` to `line_indicies` and `point_indicies`
+    fn split_off_lines_and_points(&mut self, tri_end: usize, line_count: usize){
+        let mut lines = self.indicies.split_off(tri_end);
+        let points = lines.split_off(line_count);
+        self.line_indicies.extend(lines);
+        self.point_indicies.extend(points);
+    }
+
+    /// Deduplicates the vertices referenced by vert_ind ( see `weld` ) and replaces the loaded vertex data with them
+    fn build_indicies(&mut self, vert_ind: Vec<Corner>) -> Result<(), ObjError>{
+        let welder = Welder{ pos: &self.pos_data, tex: self.tex_data.as_ref(), norm: self.norm_data.as_ref(), color: self.color_data.as_ref(), tolerance: self.weld };
+        let mut h: HashMap<Vertex, usize> = HashMap::new();
+        let mut grid = WeldGrid::new(self.weld.pos);
+        let exact = self.weld == WeldTolerance::default();
+        // The first corner of every vertex, in order of first use
+        let mut unique: Vec<Corner> = Vec::new();
+
+        for v in &vert_ind {
+            let ind = if exact {
+                *h.entry(welder.key(v)).or_insert_with(|| { unique.push(*v); unique.len() - 1 })
+            } else {
+                let (pos, len) = welder.pos_of(v);
+                match grid.find(&pos[..len], |id| welder.same(&unique[id], v)) {
+                    Some(id) => id,
+                    None => { grid.insert(&pos[..len], unique.len()); unique.push(*v); unique.len() - 1 }
+                }
             };
-    
-            let loaded_vert = Vertex::<T>{
-                pos: self.pos_data.get(process_index(v.coord_rindex, self.pos_data.len())),
-                norm: if let Some(norm_index) = v.normal_rindex{
-                    if let Some(norms) = &mut self.norm_data{
-                        Some(norms.get(process_index(norm_index, norms.len())))
-                    }else {None}
-                } else {None},
-                tex: if let Some(tex_index) = v.texcoord_rindex{
-                    if let Some(texs) = &mut self.tex_data{
-                        Some(texs.get(process_index(tex_index, texs.len())))
-                    }else {None}
-                } else {None},
+            self.indicies.push(I::try_from(ind).map_err(|_| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))?);
+        }
+
+        let gather = |attribs: &VertexAttribs<T>, index_of: &dyn Fn(&Corner) -> Option<usize>| {
+            let mut out = VertexAttribs::new(attribs.get_elem_per_vert());
+            out.resize_to(unique.len());
+            for (i, c) in unique.iter().enumerate() {
+                if let Some(j) = index_of(c) {
+                    out.set_slice(i, attribs.get_slice(j));
+                }
+            }
+            out
+        };
+        let new_pos = gather(&self.pos_data, &|c| Some(c.pos));
+        let new_tex = self.tex_data.as_ref().map(|t| gather(t, &|c| c.tex));
+        let new_norm = self.norm_data.as_ref().map(|n| gather(n, &|c| c.norm));
+        let new_color = self.color_data.as_ref().map(|c| gather(c, &|c| Some(c.pos)));
+        self.pos_data = new_pos;
+        self.tex_data = new_tex;
+        self.norm_data = new_norm;
+        self.color_data = new_color;
+        self.tangent_data = None;
+        Ok(())
+    }
+
+    /// Ends the current sub-mesh at start and begins a copy of it changed by f, an empty current sub-mesh is changed in place instead
+    fn begin_sub_mesh(&mut self, start: usize, f: impl FnOnce(&mut SubMesh)) {
+        match self.sub_meshes.last_mut() {
+            Some(last) if last.range.start == start => f(last),
+            Some(last) => {
+                last.range.end = start;
+                let mut next = SubMesh{ range: start..start, ..last.clone() };
+                f(&mut next);
+                self.sub_meshes.push(next);
+            },
+            None => {
+                let mut first = SubMesh{ object: String::new(), group: String::new(), material_name: None, material: None, range: start..start };
+                f(&mut first);
+                self.sub_meshes.push(first);
+            }
+        }
+    }
+
+    /// The indices of one sub-mesh, e.g. to give it its own IBO
+    pub fn sub_mesh_indicies(&self, sub_mesh: &SubMesh) -> &[I] {
+        &self.indicies[sub_mesh.range.clone()]
+    }
+
+    /// Loads every mtllib library ( relative to dir, the directory of the obj file ) and links them to `material_ranges`
+    pub fn load_mtl_libs(&mut self, dir: &Path, mode: ParseMode) -> Result<Vec<ObjError>, ObjError>{
+        let mut warnings: Vec<ObjError> = Vec::new();
+        let mut report = |e: ObjError| mode.report(e, &mut warnings);
+
+        for lib in &self.mtl_libs {
+            let path = dir.join(lib);
+            let f = match File::open(&path) {
+                Ok(f) => f,
+                Err(_) => { report(ObjError::new(0, lib, ObjErrorKind::MissingMaterialLibrary(lib.clone())))?; continue; }
             };
-            if let Some(already_existing_ind) = h.get(&loaded_vert){
-                self.indicies.push(I::try_from(*already_existing_ind).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Indeces too big!"))?);
-            }else{
-                h.insert(loaded_vert, curr_ind);
-                self.indicies.push(I::try_from(curr_ind).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Indeces too big!"))?);
-                curr_ind += 1;
+            let (mats, mtl_warnings) = mtl::parse_mtl(io::BufReader::new(f), path.parent().unwrap_or(dir), mode)?;
+            for w in mtl_warnings { report(w)?; }
+            self.materials.extend(mats);
+        }
+
+        for r in &mut self.material_ranges {
+            r.material = self.materials.iter().position(|m| m.name == r.name);
+            if r.material.is_none() {
+                report(ObjError::new(0, &r.name, ObjErrorKind::UnknownMaterial(r.name.clone())))?;
             }
         }
-        // Clean up
-        self.pos_data = VertexAttribs::new(self.pos_data.get_elem_per_vert());
-        self.norm_data = self.norm_data.as_ref().map(|inner|VertexAttribs::<T>::new(inner.get_elem_per_vert()));
-        self.tex_data = self.tex_data.as_ref().map(|inner|VertexAttribs::<T>::new(inner.get_elem_per_vert()));
+        let materials = &self.materials;
+        for m in &mut self.sub_meshes {
+            m.material = m.material_name.as_ref().and_then(|name| materials.iter().position(|mat| mat.name == *name));
+        }
+        Ok(warnings)
+    }
+}
 
-        self.pos_data.resize_to(h.len());
-        self.norm_data.as_mut().map(|v|{v.resize_to(h.len()); v});
-        self.tex_data.as_mut().map(|v|{v.resize_to(h.len()); v});
+/// Smallest chunk for the parallel loader, in bytes
+const MIN_PARALLEL_CHUNK: usize = 1 << 16;
 
-        for (v, i) in h {
-            self.pos_data.set(i, v.pos);
-            if let Some(texs) = &mut self.tex_data{
-                if let Some(new_tex) = v.tex{
-                    texs.set(i, new_tex);
-                }              
+/// A line that can only be handled once the vertex data of every chunk before it is known
+struct DeferredLine<'a> {
+    line: usize,
+    text: Cow<'a, str>,
+    /// How much vertex data the chunk had loaded before this line
+    counts: LoadedCounts,
+}
+
+/// Face, line and point corners, deferred statements ( after how many face corners ) and errors of a chunk
+type ChunkElements<'b, 'a> = (Vec<Corner>, LinesAndPoints, Vec<(usize, &'b DeferredLine<'a>)>, Vec<ObjError>);
+
+/// What the first, parallel, pass over a chunk of lines produced
+struct ParsedChunk<'a, T> {
+    pos_data: VertexAttribs<T>,
+    tex_data: Option<VertexAttribs<T>>,
+    norm_data: Option<VertexAttribs<T>>,
+    color_data: Option<VertexAttribs<T>>,
+    deferred: Vec<DeferredLine<'a>>,
+    errors: Vec<ObjError>,
+}
+
+/// Splits bytes into about count chunks that each end at the end of a line
+fn split_into_chunks(bytes: &[u8], count: usize) -> Vec<&[u8]> {
+    let target = (bytes.len() / count.max(1)).max(MIN_PARALLEL_CHUNK);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < bytes.len() {
+        let end = (start + target).min(bytes.len());
+        let end = match bytes[end..].iter().position(|b| *b == b'\n') {
+            Some(p) => end + p + 1,
+            None => bytes.len()
+        };
+        chunks.push(&bytes[start..end]);
+        start = end;
+    }
+    chunks
+}
+
+impl<T, I> ObjData<T, I>
+where T: FromStr + Send + Sync + ObjScalar + Default, I: FromStr + Copy + Send + TryFrom<usize>{
+    /// Same as `load_parallel_from_bytes`, but reads the file at path first
+    pub fn load_parallel_from_path(&mut self, path: &Path, mode: ParseMode) -> Result<Vec<ObjError>, ObjError>{
+        let bytes = std::fs::read(path).map_err(|e| ObjError::new(0, &path.to_string_lossy(), ObjErrorKind::Io(e.kind())))?;
+        self.load_parallel_from_bytes(&bytes, mode)
+    }
+
+    /// `load_from_bytes` on all threads of the rayon thread pool, with exactly the same result
+    pub fn load_parallel_from_bytes(&mut self, bytes: &[u8], mode: ParseMode) -> Result<Vec<ObjError>, ObjError>{
+        let chunks = split_into_chunks(bytes, rayon::current_num_threads() * 4);
+        let newlines: Vec<usize> = chunks.par_iter().map(|c| c.iter().filter(|b| **b == b'\n').count()).collect();
+        let mut first_lines: Vec<usize> = Vec::with_capacity(chunks.len());
+        let mut line = 1;
+        for n in &newlines {
+            first_lines.push(line);
+            line += n;
+        }
+
+        // First pass: vertex data, and which lines have to wait for it
+        let dims = (self.pos_data.get_elem_per_vert(), self.tex_data.as_ref().map(|t| t.get_elem_per_vert()), self.norm_data.as_ref().map(|n| n.get_elem_per_vert()), self.color_data.as_ref().map(|c| c.get_elem_per_vert()));
+        let parsed: Vec<ParsedChunk<T>> = chunks.par_iter().zip(first_lines.par_iter()).map(|(c, first_line)| Self::parse_chunk(c, *first_line, dims)).collect();
+
+        let mut errors: Vec<ObjError> = Vec::new();
+        let mut chunk_counts: Vec<LoadedCounts> = Vec::with_capacity(parsed.len());
+        let mut deferred: Vec<Vec<DeferredLine>> = Vec::with_capacity(parsed.len());
+        for p in parsed {
+            chunk_counts.push(self.loaded_counts());
+            self.pos_data.data.extend_from_slice(&p.pos_data.data);
+            if let (Some(t), Some(pt)) = (&mut self.tex_data, &p.tex_data) { t.data.extend_from_slice(&pt.data); }
+            if let (Some(n), Some(pn)) = (&mut self.norm_data, &p.norm_data) { n.data.extend_from_slice(&pn.data); }
+            if let (Some(c), Some(pc)) = (&mut self.color_data, &p.color_data) { c.data.extend_from_slice(&pc.data); }
+            errors.extend(p.errors);
+            deferred.push(p.deferred);
+        }
+
+        // Second pass: faces, now that all positions are known
+        let pos_data = &self.pos_data;
+        let faces: Vec<ChunkElements> = deferred.par_iter().zip(chunk_counts.par_iter()).map(|(lines, before)| {
+            let mut vert_ind: Vec<Corner> = Vec::new();
+            let mut elements = LinesAndPoints::default();
+            let mut statements = Vec::new();
+            let mut errors = Vec::new();
+            for l in lines {
+                let (keyword, args) = match split_line(&l.text) {
+                    Some(s) => s,
+                    None => continue
+                };
+                let counts = LoadedCounts{
+                    pos: before.pos + l.counts.pos,
+                    tex: before.tex.zip(l.counts.tex).map(|(a, b)| a + b),
+                    norm: before.norm.zip(l.counts.norm).map(|(a, b)| a + b)
+                };
+                let res = match keyword {
+                    "f" => parse_face(&args, counts, pos_data, &mut vert_ind),
+                    "l" | "p" => parse_line_element(keyword, &args, counts, &mut elements),
+                    _ => {
+                        statements.push((vert_ind.len(), l));
+                        Ok(())
+                    }
+                };
+                if let Err(kind) = res {
+                    errors.push(ObjError::new(l.line, &l.text, kind));
+                }
+            }
+            (vert_ind, elements, statements, errors)
+        }).collect();
+
+        // Objects, groups and materials depend on everything before them, but there are few of them
+        let mut vert_ind: Vec<Corner> = Vec::with_capacity(faces.iter().map(|f| f.0.len()).sum());
+        let mut elements = LinesAndPoints::default();
+        let mut smoothing = Smoothing::default();
+        let start = self.indicies.len();
+        self.begin_sub_mesh(start, |m| { m.object.clear(); m.group.clear(); m.material_name = None; });
+        for (chunk_vert_ind, chunk_elements, statements, face_errors) in faces {
+            for (offset, l) in statements {
+                smoothing.tri_groups.resize((vert_ind.len() + offset) / 3, smoothing.current);
+                if let Some((keyword, args)) = split_line(&l.text) {
+                    if let Err(kind) = self.apply_statement(keyword, &args, self.indicies.len() + vert_ind.len() + offset, &mut smoothing) {
+                        errors.push(ObjError::new(l.line, &l.text, kind));
+                    }
+                }
             }
+            vert_ind.extend_from_slice(&chunk_vert_ind);
+            elements.lines.extend(chunk_elements.lines);
+            elements.points.extend(chunk_elements.points);
+            errors.extend(face_errors);
+        }
+
+        errors.sort_by_key(|e| e.line);
+        if mode == ParseMode::Strict && !errors.is_empty() {
+            return Err(errors.swap_remove(0));
+        }
 
-            if let Some(norms) = &mut self.norm_data{
-                if let Some(new_norm) = v.norm{
-                    norms.set(i, new_norm);
-                }              
+        smoothing.tri_groups.resize(vert_ind.len() / 3, smoothing.current);
+        self.finish_ranges(self.indicies.len() + vert_ind.len());
+        self.generate_missing_normals(&mut vert_ind, &smoothing);
+        let (vert_ind, tri_end, line_count) = self.append_lines_and_points(vert_ind, elements);
+        self.build_indicies_parallel(vert_ind)?;
+        self.split_off_lines_and_points(tri_end, line_count);
+        Ok(errors)
+    }
+
+    fn parse_chunk(chunk: &[u8], first_line: usize, dims: (Dimension, Option<Dimension>, Option<Dimension>, Option<Dimension>)) -> ParsedChunk<'_, T>{
+        let mut p = ParsedChunk{
+            pos_data: VertexAttribs::new(dims.0),
+            tex_data: dims.1.map(VertexAttribs::new),
+            norm_data: dims.2.map(VertexAttribs::new),
+            color_data: dims.3.map(VertexAttribs::new),
+            deferred: Vec::new(),
+            errors: Vec::new()
+        };
+        let mut lines: Vec<&[u8]> = chunk.split(|b| *b == b'\n').collect();
+        if lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+        for (i, raw) in lines.into_iter().enumerate() {
+            let line_no = first_line + i;
+            let text = match String::from_utf8_lossy(raw) {
+                Cow::Borrowed(s) => Cow::Borrowed(s.trim_end_matches('\r')),
+                Cow::Owned(s) => Cow::Owned(s.trim_end_matches('\r').to_owned())
+            };
+            let res = match split_line(&text) {
+                None => Ok(()),
+                Some((keyword @ ("v" | "vt" | "vn"), args)) => push_vertex_line(keyword, &args, &mut p.pos_data, &mut p.tex_data, &mut p.norm_data, &mut p.color_data),
+                Some(("f" | "l" | "p" | "mtllib" | "o" | "g" | "usemtl" | "s", _)) => {
+                    let counts = LoadedCounts{
+                        pos: p.pos_data.len(),
+                        tex: p.tex_data.as_ref().map(|t| t.len()),
+                        norm: p.norm_data.as_ref().map(|n| n.len())
+                    };
+                    p.deferred.push(DeferredLine{ line: line_no, text: text.clone(), counts });
+                    Ok(())
+                },
+                Some((keyword, _)) if is_ignored_statement(keyword) => Ok(()),
+                Some((keyword, _)) => Err(ObjErrorKind::UnknownStatement(keyword.to_owned()))
+            };
+            if let Err(kind) = res {
+                p.errors.push(ObjError::new(line_no, &text, kind));
+            }
+        }
+        p
+    }
+
+    /// Same result as `build_indicies`, the vertices are split into shards by hash and every shard is deduplicated on its own ( welding within tolerances is sequential )
+    fn build_indicies_parallel(&mut self, vert_ind: Vec<Corner>) -> Result<(), ObjError>{
+        if self.weld != WeldTolerance::default() {
+            return self.build_indicies(vert_ind);
+        }
+        let pos_data = &self.pos_data;
+        let tex_data = &self.tex_data;
+        let norm_data = &self.norm_data;
+        let color_data = &self.color_data;
+        let welder = Welder{ pos: pos_data, tex: tex_data.as_ref(), norm: norm_data.as_ref(), color: color_data.as_ref(), tolerance: self.weld };
+
+        let hash_state = RandomState::new();
+        let hashes: Vec<u64> = vert_ind.par_iter().map(|v| hash_state.hash_one(welder.key(v))).collect();
+        let shard_count = rayon::current_num_threads() * 4;
+        let mut shards: Vec<Vec<usize>> = vec![Vec::new(); shard_count];
+        for (i, h) in hashes.iter().enumerate() {
+            shards[(*h % shard_count as u64) as usize].push(i);
+        }
+
+        // For every corner, the first corner that has the same vertex
+        let firsts: Vec<Vec<(usize, usize)>> = shards.par_iter().map(|shard| {
+            let mut h: HashMap<Vertex, usize> = HashMap::new();
+            shard.iter().map(|i| (*i, *h.entry(welder.key(&vert_ind[*i])).or_insert(*i))).collect()
+        }).collect();
+        let mut first_of: Vec<usize> = vec![0; vert_ind.len()];
+        for (i, first) in firsts.into_iter().flatten() {
+            first_of[i] = first;
+        }
+
+        // Number the vertices in order of first use, like the sequential loader does
+        let mut unique: Vec<usize> = Vec::new();
+        let mut id_of: Vec<usize> = vec![0; vert_ind.len()];
+        for (i, first) in first_of.iter().enumerate() {
+            if *first == i {
+                id_of[i] = unique.len();
+                unique.push(i);
+            }
+        }
+        let new_indicies: Vec<I> = first_of.par_iter()
+            .map(|first| I::try_from(id_of[*first]).map_err(|_| ObjError::new(0, "", ObjErrorKind::IndicesTooBig)))
+            .collect::<Result<_, _>>()?;
+        self.indicies.extend(new_indicies);
+
+        let gather = |attribs: &VertexAttribs<T>, index_of: &(dyn Fn(&Corner) -> Option<usize> + Sync)| {
+            let mut out = VertexAttribs::new(attribs.get_elem_per_vert());
+            out.resize_to(unique.len());
+            let dim = attribs.get_elem_per_vert() as usize;
+            out.data.par_chunks_mut(dim).zip(unique.par_iter()).for_each(|(dst, c)| {
+                if let Some(i) = index_of(&vert_ind[*c]) {
+                    dst.copy_from_slice(attribs.get_slice(i));
+                }
+            });
+            out
+        };
+        let new_pos = gather(pos_data, &|c| Some(c.pos));
+        let new_tex = tex_data.as_ref().map(|t| gather(t, &|c| c.tex));
+        let new_norm = norm_data.as_ref().map(|n| gather(n, &|c| c.norm));
+        let new_color = color_data.as_ref().map(|c| gather(c, &|c| Some(c.pos)));
+        self.pos_data = new_pos;
+        self.tex_data = new_tex;
+        self.norm_data = new_norm;
+        self.color_data = new_color;
+        self.tangent_data = None;
+        Ok(())
+    }
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy + TryInto<usize> + TryFrom<usize>{
+    /// Fills `tangent_data` with MikkTSpace tangents, splitting vertices whose corners get different ones ( needs 3D positions, 3D normals and 2D texture coordinates )
+    pub fn generate_tangents(&mut self) -> Result<(), ObjError>{
+        let missing = |what| ObjError::new(0, "", ObjErrorKind::MissingAttribute(what));
+        if self.pos_data.get_elem_per_vert() != Dimension::ThreeDim {
+            return Err(missing("3D positions"));
+        }
+        let norms = match &self.norm_data {
+            Some(n) if n.get_elem_per_vert() == Dimension::ThreeDim => n,
+            _ => return Err(missing("3D normals"))
+        };
+        let texs = match &self.tex_data {
+            Some(t) if t.get_elem_per_vert() == Dimension::TwoDim => t,
+            _ => return Err(missing("2D texture coordinates"))
+        };
+        // They are replaced anyway
+        self.tangent_data = None;
+        self.check_attribute_counts()?;
+        let vertex_count = self.pos_data.len();
+        let triangles = self.checked_triangles()?;
+
+        let to_f64 = |d: &[T]| -> Vec<f64> { d.iter().map(|v| v.to_f64()).collect() };
+        let positions: Vec<[f64; 3]> = to_f64(&self.pos_data.data).chunks(3).map(|p| [p[0], p[1], p[2]]).collect();
+        let normals: Vec<[f64; 3]> = to_f64(&norms.data).chunks(3).map(|n| [n[0], n[1], n[2]]).collect();
+        let tex_coords: Vec<[f64; 2]> = to_f64(&texs.data).chunks(2).map(|t| [t[0], t[1]]).collect();
+        let generated = tangents::generate_tangents(&positions, &normals, &tex_coords, &triangles);
+
+        // The first tangent a vertex gets stays with it, every other one gets a copy of the vertex
+        let mut tangents: Vec<Option<[f64; 4]>> = vec![None; vertex_count];
+        let mut copies: HashMap<(usize, [u64; 4]), usize> = HashMap::new();
+        let mut copied_from: Vec<usize> = Vec::new();
+        let mut new_indicies: Vec<usize> = Vec::with_capacity(self.indicies.len());
+        for (v, t) in triangles.iter().flatten().zip(generated) {
+            match tangents[*v] {
+                None => tangents[*v] = Some(t),
+                Some(first) if first == t => {},
+                Some(_) => {
+                    let copy = *copies.entry((*v, t.map(f64::to_bits))).or_insert_with(|| {
+                        copied_from.push(*v);
+                        tangents.push(Some(t));
+                        vertex_count + copied_from.len() - 1
+                    });
+                    new_indicies.push(copy);
+                    continue;
+                }
+            }
+            new_indicies.push(*v);
+        }
+
+        if !copied_from.is_empty() {
+            self.indicies = new_indicies.into_iter().map(|v| I::try_from(v).map_err(|_| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))).collect::<Result<_, _>>()?;
+            let others = self.tex_data.iter_mut().chain(self.norm_data.iter_mut()).chain(self.color_data.iter_mut());
+            for attribs in std::iter::once(&mut self.pos_data).chain(others) {
+                for v in &copied_from {
+                    let copy = attribs.get_slice(*v).to_vec();
+                    attribs.data.extend(copy);
+                }
+            }
+        }
+        let mut tangent_data = VertexAttribs::new(Dimension::FourDim);
+        // Vertices that no triangle uses ( only lines or points ) get any tangent
+        tangent_data.data = tangents.iter().flat_map(|t| t.unwrap_or([1.0, 0.0, 0.0, 1.0])).map(T::from_f64).collect();
+        self.tangent_data = Some(tangent_data);
+        Ok(())
+    }
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy + TryInto<usize>{
+    /// Fails if an attribute doesn't have one element per position, it couldn't be reordered or copied together with them
+    pub(crate) fn check_attribute_counts(&self) -> Result<(), ObjError>{
+        let expected = self.pos_data.len();
+        let attribs = [("texture coordinates", &self.tex_data), ("normals", &self.norm_data), ("tangents", &self.tangent_data), ("colors", &self.color_data)];
+        for &(attribute, a) in attribs.iter() {
+            if let Some(a) = a {
+                if a.len() != expected {
+                    return Err(ObjError::new(0, "", ObjErrorKind::AttributeCountMismatch{ attribute, expected, found: a.len() }));
+                }
             }
         }
         Ok(())
     }
+
+    /// `triangles`, failing if an index is past the last position
+    pub(crate) fn checked_triangles(&self) -> Result<Vec<[usize; 3]>, ObjError>{
+        let triangles = self.triangles()?;
+        let loaded = self.pos_data.len();
+        if let Some(v) = triangles.iter().flatten().find(|v| **v >= loaded) {
+            return Err(ObjError::new(0, "", ObjErrorKind::IndexOutOfRange{ attribute: "position", index: v.to_string(), loaded }));
+        }
+        Ok(triangles)
+    }
+
+    /// The indices in the smallest type that fits them, see `IndexBuffer`
+    pub fn compact_indicies(&self) -> Result<IndexBuffer, ObjError>{
+        IndexBuffer::from_indicies(&self.indicies).ok_or_else(|| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))
+    }
+
+    /// `line_indicies` in the smallest type that fits them
+    pub fn compact_line_indicies(&self) -> Result<IndexBuffer, ObjError>{
+        IndexBuffer::from_indicies(&self.line_indicies).ok_or_else(|| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))
+    }
+
+    /// `point_indicies` in the smallest type that fits them
+    pub fn compact_point_indicies(&self) -> Result<IndexBuffer, ObjError>{
+        IndexBuffer::from_indicies(&self.point_indicies).ok_or_else(|| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))
+    }
+
+    /// indicies as triangles of usize
+    pub(crate) fn triangles(&self) -> Result<Vec<[usize; 3]>, ObjError>{
+        let mut triangles: Vec<[usize; 3]> = Vec::with_capacity(self.indicies.len() / 3);
+        for t in self.indicies.chunks_exact(3) {
+            let mut tri = [0usize; 3];
+            for k in 0..3 {
+                tri[k] = t[k].try_into().map_err(|_| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))?;
+            }
+            triangles.push(tri);
+        }
+        Ok(triangles)
+    }
+
+    /// Writes the mesh as obj, loading it again gives the same `ObjData` apart from materials and tangents
+    pub fn write_obj<W: Write>(&self, w: W, options: &WriteOptions) -> io::Result<()>{
+        self.write_obj_with_libs(w, options, &self.mtl_libs)
+    }
+
+    /// Writes the mesh to path and, if there are materials, a .mtl file with the same name next to it
+    pub fn save(&self, path: &Path, options: &WriteOptions) -> io::Result<()>{
+        let mut libs = self.mtl_libs.clone();
+        if options.materials && !self.materials.is_empty() {
+            let mtl_path = path.with_extension("mtl");
+            let dir = path.parent().unwrap_or_else(|| Path::new(""));
+            mtl::write_mtl(io::BufWriter::new(File::create(&mtl_path)?), &self.materials, dir)?;
+            libs = mtl_path.file_name().map(|n| n.to_string_lossy().into_owned()).into_iter().collect();
+        }
+        self.write_obj_with_libs(io::BufWriter::new(File::create(path)?), options, &libs)
+    }
+
+    fn write_obj_with_libs<W: Write>(&self, mut w: W, options: &WriteOptions, mtl_libs: &[String]) -> io::Result<()>{
+        let vert_count = self.pos_data.len();
+        // Only write attributes that every vertex has, so that every face can use the same index for all of them
+        let usable = |attribs: &Option<VertexAttribs<T>>, wanted: bool, name: &str| -> io::Result<bool> {
+            match attribs {
+                Some(a) if wanted && a.len() != vert_count =>
+                    Err(io::Error::new(io::ErrorKind::InvalidInput, format!("there are {} {} for {} positions", a.len(), name, vert_count))),
+                Some(_) => Ok(wanted),
+                None => Ok(false)
+            }
+        };
+        let with_tex = usable(&self.tex_data, options.tex_coords, "texture coordinates")?;
+        let with_norm = usable(&self.norm_data, options.normals, "normals")?;
+        // Only rgb fits in a v line
+        let with_color = usable(&self.color_data, options.colors, "colours")? && self.color_data.as_ref().is_some_and(|c| c.get_elem_per_vert() as usize >= 3);
+
+        if options.materials && !mtl_libs.is_empty() {
+            writeln!(w, "mtllib {}", mtl_libs.join(" "))?;
+        }
+        write_vertex_data(&mut w, "v", &self.pos_data, self.color_data.as_ref().filter(|_| with_color), options.precision)?;
+        if with_tex {
+            if let Some(t) = &self.tex_data { write_vertex_data(&mut w, "vt", t, None, options.precision)?; }
+        }
+        if with_norm {
+            if let Some(n) = &self.norm_data { write_vertex_data(&mut w, "vn", n, None, options.precision)?; }
+        }
+
+        let triangles = self.triangles()?;
+        // Lines and points use the same corners as faces, so that they get the same vertices when loaded again
+        let write_corner = |w: &mut W, i: usize| -> io::Result<()> {
+            match (with_tex, with_norm) {
+                (true, true) => write!(w, " {0}/{0}/{0}", i + 1),
+                (true, false) => write!(w, " {0}/{0}", i + 1),
+                (false, true) => write!(w, " {0}//{0}", i + 1),
+                (false, false) => write!(w, " {}", i + 1),
+            }
+        };
+        let write_faces = |w: &mut W, range: Range<usize>| -> io::Result<()> {
+            for t in &triangles[range.start / 3..range.end / 3] {
+                write!(w, "f")?;
+                for i in t {
+                    write_corner(w, *i)?;
+                }
+                writeln!(w)?;
+            }
+            Ok(())
+        };
+
+        let (mut object, mut group, mut material): (&str, &str, Option<&str>) = ("", "", None);
+        let mut written = 0;
+        for m in &self.sub_meshes {
+            write_faces(&mut w, written..m.range.start)?;
+            if options.groups {
+                // A sub-mesh with the same names as the one before it still needs a statement to start it
+                let same_names = m.object == object && m.material_name.as_deref() == material;
+                if m.object != object {
+                    writeln!(w, "o {}", m.object)?;
+                    object = &m.object;
+                    group = "";
+                }
+                if m.group != group || (same_names && m.range.start > 0) {
+                    writeln!(w, "g {}", m.group)?;
+                    group = &m.group;
+                }
+            }
+            if let (true, Some(name)) = (options.materials, &m.material_name) {
+                if material != Some(name.as_str()) {
+                    writeln!(w, "usemtl {}", name)?;
+                    material = Some(name);
+                }
+            }
+            write_faces(&mut w, m.range.clone())?;
+            written = m.range.end;
+        }
+        write_faces(&mut w, written..self.indicies.len())?;
+
+        let to_usize = |i: &I| -> io::Result<usize> {
+            (*i).try_into().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "index doesn't fit into usize"))
+        };
+        // Segments that continue where the last one ended are joined into one polyline
+        let mut last_end: Option<usize> = None;
+        for s in self.line_indicies.chunks_exact(2) {
+            let (a, b) = (to_usize(&s[0])?, to_usize(&s[1])?);
+            if last_end != Some(a) {
+                if last_end.is_some() {
+                    writeln!(w)?;
+                }
+                write!(w, "l")?;
+                write_corner(&mut w, a)?;
+            }
+            write_corner(&mut w, b)?;
+            last_end = Some(b);
+        }
+        if last_end.is_some() {
+            writeln!(w)?;
+        }
+        if !self.point_indicies.is_empty() {
+            write!(w, "p")?;
+            for i in &self.point_indicies {
+                write_corner(&mut w, to_usize(i)?)?;
+            }
+            writeln!(w)?;
+        }
+        w.flush()
+    }
+}
+
+/// What `ObjData::write_obj` puts in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Digits after the decimal point, None writes the shortest number that is read back as the same value
+    pub precision: Option<usize>,
+    pub tex_coords: bool,
+    pub normals: bool,
+    /// o and g statements for the sub-meshes
+    pub groups: bool,
+    /// Vertex colours as `v x y z r g b`, an alpha channel is left out
+    pub colors: bool,
+    /// mtllib and usemtl statements, and the .mtl file written by `ObjData::save`
+    pub materials: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self{ precision: None, tex_coords: true, normals: true, groups: true, colors: true, materials: true }
+    }
+}
+
+/// Writes one line per element of attribs, starting with keyword and followed by the rgb of colors if there are any
+fn write_vertex_data<T: ObjScalar + Default, W: Write>(w: &mut W, keyword: &str, attribs: &VertexAttribs<T>, colors: Option<&VertexAttribs<T>>, precision: Option<usize>) -> io::Result<()>{
+    for (i, element) in attribs.data.chunks(attribs.get_elem_per_vert() as usize).enumerate() {
+        write!(w, "{}", keyword)?;
+        let rgb = colors.map(|c| &c.data[i * c.get_elem_per_vert() as usize..][..3]).unwrap_or(&[]);
+        for v in element.iter().chain(rgb) {
+            let x = v.to_f64();
+            match precision {
+                Some(p) => write!(w, " {:.*}", p, x)?,
+                None => {
+                    // Values that came from a f32 are much shorter when written as one
+                    let short = (x as f32).to_string();
+                    if short.parse::<f64>().is_ok_and(|s| T::from_f64(s).to_f64() == x) {
+                        write!(w, " {}", short)?;
+                    } else {
+                        write!(w, " {}", x)?;
+                    }
+                }
+            }
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write as _;
+
+    /// Reproducible obj text with every kind of statement, indices up to 200 back and a few bad lines
+    fn generated_obj(lines: usize) -> String {
+        let mut seed: u64 = 1;
+        let mut next = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) % n
+        };
+        let mut s = String::new();
+        let (mut v, mut vt) = (0, 0);
+        for i in 0..lines {
+            match next(12) {
+                0..=4 => { writeln!(s, "v {} {} {}", next(100), next(100), next(100)).unwrap(); v += 1; },
+                5 => { writeln!(s, "vt 0.{} 0.{}", next(100), next(100)).unwrap(); vt += 1; },
+                6..=8 if v >= 3 && vt >= 1 => {
+                    s.push('f');
+                    for _ in 0..3 + next(3) {
+                        let back = 1 + next(v.min(200));
+                        let t = 1 + next(vt);
+                        if next(2) == 0 {
+                            write!(s, " -{}/{}", back, t).unwrap();
+                        } else {
+                            write!(s, " {}/-{}", v + 1 - back, vt + 1 - t).unwrap();
+                        }
+                    }
+                    s.push('\n');
+                },
+                9 => writeln!(s, "usemtl m{}", next(4)).unwrap(),
+                10 => writeln!(s, "{} n{}", ["g", "o", "s"][next(3) as usize], next(3)).unwrap(),
+                _ => if i % 50 == 0 { s.push_str("f 1 x 2\n"); }
+            }
+        }
+        s
+    }
+
+    fn new_obj(tex: bool, norm: bool) -> ObjData<f32, u32> {
+        ObjData::new(Dimension::ThreeDim, if tex { Some(Dimension::TwoDim) } else { None }, if norm { Some(Dimension::ThreeDim) } else { None })
+    }
+
+    /// Writes o and loads the result again with the same dimensions
+    fn write_and_load(o: &ObjData<f32, u32>, options: &WriteOptions) -> (String, ObjData<f32, u32>) {
+        let mut written: Vec<u8> = Vec::new();
+        o.write_obj(&mut written, options).unwrap();
+        let mut reloaded = new_obj(o.tex_data.is_some(), o.norm_data.is_some());
+        let warnings = reloaded.load_from_bytes(&written, ParseMode::Strict, None).unwrap();
+        assert!(warnings.is_empty());
+        (String::from_utf8(written).unwrap(), reloaded)
+    }
+
+    const GROUPED_NGONS: &str = "mtllib cube.mtl\n\
+        v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0.5 1.5 0\nv 0 0 1\nv 1 0 1\n\
+        vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\nvn 0 -1 0\n\
+        o house\ng walls\nusemtl brick\nf 1/1/1 2/2/1 3/3/1 5/4/1 4/4/1\n\
+        g floor\nusemtl wood\nf -7/-4/-1 -6/-3/-1 -1/-2/-1 -2/-1/-1\n\
+        o shed\nusemtl brick\nf 1/1/1 -6/-3/-2 -5/-2/-2\n";
+
+    #[test]
+    fn round_trip_ngons_negative_indices_and_groups() {
+        let mut o = new_obj(true, true);
+        o.load_from_bytes(GROUPED_NGONS.as_bytes(), ParseMode::Strict, None).unwrap();
+        assert_eq!(o.indicies.len(), (3 + 2 + 1) * 3);
+        let (_, reloaded) = write_and_load(&o, &WriteOptions::default());
+        assert!(o == reloaded);
+        assert_eq!(reloaded.sub_meshes.iter().map(|m| m.name()).collect::<Vec<_>>(), o.sub_meshes.iter().map(|m| m.name()).collect::<Vec<_>>());
+
+        // Without groups and materials only the geometry is kept
+        let options = WriteOptions{ groups: false, materials: false, ..WriteOptions::default() };
+        let (text, reloaded) = write_and_load(&o, &options);
+        assert!(!text.contains("usemtl") && !text.contains("\ng ") && !text.contains("\no "));
+        assert_eq!((&reloaded.pos_data, &reloaded.indicies), (&o.pos_data, &o.indicies));
+        assert!(reloaded.material_ranges.is_empty());
+    }
+
+    #[test]
+    fn sub_meshes_split_by_object_group_and_material() {
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\n\
+                   o house\ng walls\nusemtl brick\nf 1 2 3 4\nf 1 3 4\ng floor\nusemtl wood\nf 4 3 2 1\n\
+                   o shed\nusemtl brick\nf 1 2 3\ng empty\nusemtl unused\n";
+        let mut o = new_obj(false, false);
+        o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap();
+        let ranges: Vec<(String, Option<&str>, Range<usize>)> = o.sub_meshes.iter().map(|m| (m.name(), m.material_name.as_deref(), m.range.clone())).collect();
+        assert_eq!(ranges, [
+            ("".to_owned(), None, 0..3),
+            ("house/walls".to_owned(), Some("brick"), 3..12),
+            ("house/floor".to_owned(), Some("wood"), 12..18),
+            ("shed".to_owned(), Some("brick"), 18..21),
+        ]);
+        assert_eq!(o.sub_mesh_indicies(&o.sub_meshes[3]), &o.indicies[18..]);
+        // The vertices are shared, the first corner of shed is the first vertex of the walls
+        assert_eq!(o.indicies[18], o.indicies[3]);
+    }
+
+    #[test]
+    fn strict_mode_fails_on_every_polygon_error() {
+        let src = "v 0 0 0\nv 1 1 0\nv 1 0 0\nv 0 1 0\nv 2 2 0\nf 1 2 3 4\nf 1 2 5\nf 1 3 2\n";
+        let mut o = new_obj(false, false);
+        let warnings = o.load_from_bytes(src.as_bytes(), ParseMode::Lenient, None).unwrap();
+        let kinds: Vec<_> = warnings.into_iter().map(|w| (w.line, w.kind)).collect();
+        assert_eq!(kinds, [(6, ObjErrorKind::SelfIntersectingPolygon), (7, ObjErrorKind::DegeneratePolygon)]);
+        assert_eq!(o.indicies.len(), 3);
+        for src in [src, &src[..src.find("f 1 2 5").unwrap()], &src.replace("f 1 2 3 4\n", "")] {
+            let e = new_obj(false, false).load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap_err();
+            assert!(matches!(e.kind, ObjErrorKind::SelfIntersectingPolygon | ObjErrorKind::DegeneratePolygon));
+        }
+
+        // Material libraries are reported the same way
+        let mut o = new_obj(false, false);
+        o.load_from_bytes(b"mtllib missing.mtl\nusemtl red\n", ParseMode::Strict, None).unwrap();
+        let dir = Path::new("does/not/exist");
+        assert_eq!(o.load_mtl_libs(dir, ParseMode::Strict).unwrap_err().kind, ObjErrorKind::MissingMaterialLibrary("missing.mtl".to_owned()));
+        let warnings = o.load_mtl_libs(dir, ParseMode::Lenient).unwrap();
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn progress_and_total_bytes() {
+        let src = format!("{}{}", "# comment\n".repeat(2 * PROGRESS_INTERVAL), GROUPED_NGONS);
+        let lines = 2 * PROGRESS_INTERVAL + GROUPED_NGONS.lines().count();
+        let mut calls: Vec<LoadProgress> = Vec::new();
+        let mut o = new_obj(true, true);
+        o.load_from_bytes(src.as_bytes(), ParseMode::Strict, Some(&mut |p| calls.push(p))).unwrap();
+        let total = Some(src.len() as u64);
+        assert_eq!(calls, [
+            LoadProgress{ bytes_read: 10 * PROGRESS_INTERVAL as u64, total_bytes: total, lines_read: PROGRESS_INTERVAL },
+            LoadProgress{ bytes_read: 20 * PROGRESS_INTERVAL as u64, total_bytes: total, lines_read: 2 * PROGRESS_INTERVAL },
+            LoadProgress{ bytes_read: src.len() as u64, total_bytes: total, lines_read: lines },
+        ]);
+
+        // A reader doesn't know its size, and a small buffer gives the same mesh
+        let mut last = None;
+        let mut streamed = new_obj(true, true);
+        streamed.load_from_reader(io::BufReader::with_capacity(7, src.as_bytes()), ParseMode::Strict, Some(&mut |p| last = Some(p))).unwrap();
+        assert_eq!(last, Some(LoadProgress{ bytes_read: src.len() as u64, total_bytes: None, lines_read: lines }));
+        assert!(streamed == o);
+
+        let path = std::env::temp_dir().join(format!("gl_puck_progress_{}.obj", std::process::id()));
+        std::fs::write(&path, GROUPED_NGONS).unwrap();
+        let mut last = None;
+        let mut from_path = new_obj(true, true);
+        let res = from_path.load_from_path(&path, ParseMode::Strict, Some(&mut |p| last = Some(p)));
+        std::fs::remove_file(&path).unwrap();
+        res.unwrap();
+        assert_eq!(last.and_then(|p| p.total_bytes), Some(GROUPED_NGONS.len() as u64));
+        assert!(from_path == o);
+    }
+
+    #[test]
+    fn vertex_colors_follow_their_positions() {
+        let mut o = new_obj(false, false);
+        o.color_data = Some(VertexAttribs::new(Dimension::FourDim));
+        // v 4 repeats v 1 with another colour, v 5 repeats it with the same one
+        let src = "v 0 0 0 1 0 0\nv 1 0 0\nv 0 1 0 0 0.5 1\nv 0 0 0 0 1 0\nv 0 0 0 1 0 0\nf 1 2 3\nf 4 2 3\nf 5 2 3\n";
+        assert!(o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap().is_empty());
+        // Without rgb the colour is white, missing alpha is 1
+        assert_eq!(o.color_data.as_ref().unwrap().data, [1.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.5, 1.0, 1.0, 0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(o.pos_data.len(), 4);
+        assert_eq!(o.indicies, [0, 1, 2, 3, 1, 2, 0, 1, 2]);
+
+        let (text, reloaded) = {
+            let mut written: Vec<u8> = Vec::new();
+            o.write_obj(&mut written, &WriteOptions::default()).unwrap();
+            let mut reloaded = new_obj(false, false);
+            reloaded.color_data = Some(VertexAttribs::new(Dimension::FourDim));
+            reloaded.load_from_bytes(&written, ParseMode::Strict, None).unwrap();
+            (String::from_utf8(written).unwrap(), reloaded)
+        };
+        assert!(text.starts_with("v 0 0 0 1 0 0\n"));
+        assert!(reloaded == o);
+
+        let (text, _) = write_and_load(&o, &WriteOptions{ colors: false, ..WriteOptions::default() });
+        assert!(text.starts_with("v 0 0 0\n"));
+    }
+
+    #[test]
+    fn compact_indices_pick_the_smallest_type() {
+        assert_eq!(IndexBuffer::from_indicies::<u32>(&[]), Some(IndexBuffer::U8(Vec::new())));
+        assert_eq!(IndexBuffer::from_indicies(&[0u32, 255, 7]), Some(IndexBuffer::U8(vec![0, 255, 7])));
+        assert_eq!(IndexBuffer::from_indicies(&[0u32, 256]), Some(IndexBuffer::U16(vec![0, 256])));
+        assert_eq!(IndexBuffer::from_indicies(&[65535u64]), Some(IndexBuffer::U16(vec![65535])));
+        assert_eq!(IndexBuffer::from_indicies(&[65536u64, 3]), Some(IndexBuffer::U32(vec![65536, 3])));
+        assert_eq!(IndexBuffer::from_indicies(&[u32::MAX as u64 + 1]), None);
+
+        let mut o = new_obj(false, false);
+        o.indicies = (0..300).collect();
+        o.line_indicies = vec![1, 2];
+        let compact = o.compact_indicies().unwrap();
+        assert_eq!((compact.index_size(), compact.len(), compact.get(299)), (2, 300, 299));
+        assert_eq!(o.compact_line_indicies().unwrap(), IndexBuffer::U8(vec![1, 2]));
+        assert!(o.compact_point_indicies().unwrap().is_empty());
+
+        let mut big: ObjData<f32, u64> = ObjData::new(Dimension::ThreeDim, None, None);
+        big.indicies = vec![0, 1, u64::MAX];
+        assert_eq!(big.compact_indicies().unwrap_err().kind, ObjErrorKind::IndicesTooBig);
+    }
+
+    #[test]
+    fn lines_and_points_share_the_vertex_pool() {
+        let mut o = new_obj(false, false);
+        let src = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nf 1 2 3\nl 1 2 -1\np 4 3\n";
+        assert!(o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap().is_empty());
+        assert_eq!(o.pos_data.len(), 4);
+        assert_eq!(o.indicies, [0, 1, 2]);
+        // A polyline becomes segments
+        assert_eq!(o.line_indicies, [0, 1, 1, 3]);
+        assert_eq!(o.point_indicies, [3, 2]);
+        // They aren't part of sub-meshes
+        assert_eq!(o.sub_meshes.iter().map(|m| m.range.clone()).collect::<Vec<_>>(), [0..3]);
+
+        let (text, reloaded) = write_and_load(&o, &WriteOptions::default());
+        assert!(text.contains("\nl 1 2 4\np 4 3\n"), "{}", text);
+        assert!(reloaded == o);
+
+        let e = new_obj(false, false).load_from_bytes(b"v 0 0 0\nl 1\n", ParseMode::Strict, None).unwrap_err();
+        assert_eq!((e.line, e.kind), (2, ObjErrorKind::TooFewVertices(1)));
+    }
+
+    #[test]
+    fn indices_are_checked_against_what_is_loaded_so_far() {
+        let src = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\n\
+            f 1 2 4\nv 1 1 0\nf 1 2 4\nf -5 1 2\nf 0 1 2\nf 1/2 2/1 3/1\nl 1 9\np -6\nf 1 2 3\n";
+        let mut o = new_obj(true, false);
+        let warnings = o.load_from_bytes(src.as_bytes(), ParseMode::Lenient, None).unwrap();
+        let found: Vec<(usize, String)> = warnings.iter().map(|e| (e.line, e.kind.to_string())).collect();
+        assert_eq!(found, [
+            // v 4 comes after this face
+            (5, "position index '4' is out of range (3 loaded so far)".to_owned()),
+            (8, "position index '-5' is out of range (4 loaded so far)".to_owned()),
+            (9, "'0' is not a valid index".to_owned()),
+            (10, "texture coordinate index '2' is out of range (1 loaded so far)".to_owned()),
+            (11, "position index '9' is out of range (4 loaded so far)".to_owned()),
+            (12, "position index '-6' is out of range (4 loaded so far)".to_owned()),
+        ]);
+        // Broken elements are skipped
+        assert_eq!(o.indicies.len(), 6);
+        assert!(o.line_indicies.is_empty() && o.point_indicies.is_empty());
+
+        let e = new_obj(true, false).load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap_err();
+        assert_eq!((e.line, e.kind.clone()), (5, ObjErrorKind::IndexOutOfRange{ attribute: "position", index: "4".to_owned(), loaded: 3 }));
+        let parallel = new_obj(true, false).load_parallel_from_bytes(src.as_bytes(), ParseMode::Lenient).unwrap();
+        assert_eq!(parallel, warnings);
+    }
+
+    #[test]
+    fn welding_within_tolerances() {
+        let load = |src: &str, weld: WeldTolerance| {
+            let mut o = new_obj(true, false);
+            o.weld = weld;
+            o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap();
+            (o.pos_data.len(), o.indicies)
+        };
+        let tol = |pos, tex| WeldTolerance{ pos, tex, ..WeldTolerance::default() };
+        // 0.0999 and 0.1001 are on both sides of a grid line but still within 0.001
+        let straddling = "v 0.0999 0 0\nv 1 0 0\nv 0 1 0\nv 0.1001 0 0\nvt 0 0\nf 1/1 2/1 3/1\nf 4/1 3/1 2/1\n";
+        assert_eq!(load(straddling, tol(None, None)), (4, vec![0, 1, 2, 3, 2, 1]));
+        assert_eq!(load(straddling, tol(Some(0.001), None)), (3, vec![0, 1, 2, 0, 2, 1]));
+        assert_eq!(load(straddling, tol(Some(0.0001), None)).0, 4);
+        // Every attribute has to be close, the first corner's values are kept
+        let tex = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 0.01 0\nvt 0.5 0\nf 1/1 2/1 3/1\nf 1/2 3/1 2/1\nf 1/3 2/1 3/1\n";
+        assert_eq!(load(tex, tol(Some(0.1), None)).0, 5);
+        assert_eq!(load(tex, tol(None, Some(0.1))), (4, vec![0, 1, 2, 0, 2, 1, 3, 1, 2]));
+
+        // The parallel loader welds the same way
+        let mut text = String::new();
+        for i in 0..2000 {
+            let x = i as f64 * 0.05;
+            writeln!(text, "v {} 0 0\nv {} 1 0\nv {} 0 1", x + 0.0001, x - 0.0001, x).unwrap();
+        }
+        text.push_str("vt 0 0\n");
+        for i in 0..2000 {
+            writeln!(text, "f {}/1 {}/1 {}/1", i * 3 + 1, i * 3 + 2, i * 3 + 3).unwrap();
+        }
+        let mut sequential = new_obj(true, false);
+        sequential.weld = tol(Some(0.001), None);
+        sequential.load_from_bytes(text.as_bytes(), ParseMode::Strict, None).unwrap();
+        let mut parallel = new_obj(true, false);
+        parallel.weld = sequential.weld;
+        parallel.load_parallel_from_bytes(text.as_bytes(), ParseMode::Strict).unwrap();
+        assert!(sequential == parallel);
+        assert_eq!(sequential.pos_data.len(), 2000 * 3);
+    }
+
+    /// A scalar like the `Hash + Eq` wrappers that loading used to need
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+    struct Millis(i64);
+
+    impl FromStr for Millis {
+        type Err = std::num::ParseFloatError;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            s.parse::<f64>().map(Self::from_f64)
+        }
+    }
+
+    impl ObjScalar for Millis {
+        fn to_f64(self) -> f64 { self.0 as f64 / 1000.0 }
+        fn from_f64(v: f64) -> Self { Millis((v * 1000.0).round() as i64) }
+    }
+
+    #[test]
+    fn custom_scalars() {
+        let mut o: ObjData<Millis, u32> = ObjData::new(Dimension::ThreeDim, None, Some(Dimension::ThreeDim));
+        o.normal_generation = Some(NormalGeneration::Flat);
+        o.load_from_bytes(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1.0004 0 0\nf 1 2 3\nf 3 4 1\n", ParseMode::Strict, None).unwrap();
+        // 1.0004 is stored as 1, the faces point opposite ways so each gets its own vertices
+        assert_eq!(o.pos_data.data[3..6], [Millis(1000), Millis(0), Millis(0)]);
+        assert_eq!(o.indicies.len(), 6);
+        assert_eq!(o.norm_data.as_ref().unwrap().data[..3], [Millis(0), Millis(0), Millis(1000)]);
+    }
+
+    #[test]
+    fn smoothing_groups_decide_which_faces_share_normals() {
+        let src = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 1\nv 1 -1 1\nv 0 2 1\n\
+                   s 1\nf 1 2 3\nf 2 4 3\ns off\nf 1 5 2\ns 1\nf 3 4 6\n";
+        let mut o = new_obj(false, true);
+        o.normal_generation = Some(NormalGeneration::Smooth(normals::NormalWeighting::Area));
+        o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap();
+        let normal = |corner: usize| o.norm_data.as_ref().unwrap().get_vec3(o.indicies[corner] as usize);
+        let close = |corner: usize, n: Vec3| assert!((normal(corner) - n.normalize()).length() < 1e-6, "{}: {:?}", corner, normal(corner));
+
+        // Position 2 is shared by both faces of group 1, the face without a group gets its own copy
+        assert_eq!(o.indicies[1], o.indicies[3]);
+        assert_ne!(o.indicies[1], o.indicies[8]);
+        close(1, Vec3::new(-1.0, -1.0, 2.0));
+        close(8, Vec3::new(0.0, 1.0, 1.0));
+        close(6, Vec3::new(0.0, 1.0, 1.0));
+        // Going back to group 1 joins the faces from before s off
+        assert_eq!(o.indicies[2], o.indicies[9]);
+        close(2, Vec3::new(-2.0, -2.0, 3.0));
+        close(0, Vec3::Z);
+
+        // Flat gives every face its own normal
+        let mut flat = new_obj(false, true);
+        flat.normal_generation = Some(NormalGeneration::Flat);
+        flat.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap();
+        assert_ne!(flat.indicies[1], flat.indicies[3]);
+        let n = flat.norm_data.as_ref().unwrap().get_vec3(flat.indicies[3] as usize);
+        assert!((n - Vec3::new(-1.0, -1.0, 1.0).normalize()).length() < 1e-6);
+    }
+
+    #[test]
+    fn round_trip_missing_tex_coords_and_normals() {
+        // Only positions in the file
+        let src = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\nf -4 -2 -1\n";
+        let mut o = new_obj(false, false);
+        o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap();
+        let (text, reloaded) = write_and_load(&o, &WriteOptions::default());
+        assert!(!text.contains("vt") && !text.contains("vn") && !text.contains('/'));
+        assert!(o == reloaded);
+
+        // Texture coordinates and normals that are there but not written are dropped
+        let mut o = new_obj(true, true);
+        o.load_from_bytes(GROUPED_NGONS.as_bytes(), ParseMode::Strict, None).unwrap();
+        let options = WriteOptions{ tex_coords: false, normals: false, ..WriteOptions::default() };
+        let mut written: Vec<u8> = Vec::new();
+        o.write_obj(&mut written, &options).unwrap();
+        let text = String::from_utf8(written).unwrap();
+        assert!(!text.contains("vt") && !text.contains("vn"));
+        let mut reloaded = new_obj(false, false);
+        reloaded.load_from_bytes(text.as_bytes(), ParseMode::Strict, None).unwrap();
+        assert_eq!(reloaded.indicies.len(), o.indicies.len());
+    }
+
+    #[test]
+    fn round_trip_materials() {
+        let mtl_src = "newmtl brick\nKd 0.5 0.25 0.125\nNs 10\nd 0.5\nmap_Kd tex/brick.png\nnewmtl wood\nKa 0.1 0.2 0.3\nbump wood_n.png\n";
+        let dir = Path::new("assets");
+        let (materials, warnings) = mtl::parse_mtl(mtl_src.as_bytes(), dir, ParseMode::Strict).unwrap();
+        assert!(warnings.is_empty());
+        let mut o = new_obj(true, true);
+        o.load_from_bytes(GROUPED_NGONS.as_bytes(), ParseMode::Strict, None).unwrap();
+        o.materials = materials;
+
+        let mut written_mtl: Vec<u8> = Vec::new();
+        mtl::write_mtl(&mut written_mtl, &o.materials, dir).unwrap();
+        let (reloaded_materials, _) = mtl::parse_mtl(&written_mtl[..], dir, ParseMode::Strict).unwrap();
+        assert_eq!(reloaded_materials, o.materials);
+        assert_eq!(reloaded_materials[0].diffuse_map.as_deref(), Some(Path::new("assets/tex/brick.png")));
+
+        let (text, mut reloaded) = write_and_load(&o, &WriteOptions::default());
+        assert!(text.starts_with("mtllib cube.mtl\n"));
+        reloaded.materials = reloaded_materials;
+        assert!(o == reloaded);
+        assert_eq!(reloaded.material_ranges.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), ["brick", "wood", "brick"]);
+    }
+
+    #[test]
+    fn round_trip_precision() {
+        let src = "v 0.1 0.123456 -2.5\nv 1 0 1e-7\nv 0.3333333 1 0\nvt 0.1 0.9\nf 1/1 2/1 3/1\n";
+        let mut o = new_obj(true, false);
+        o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap();
+
+        // The shortest numbers that read back as the same f32
+        let (text, reloaded) = write_and_load(&o, &WriteOptions::default());
+        assert!(text.contains("v 0.1 0.123456 -2.5\n") && text.contains("vt 0.1 0.9\n"));
+        assert!(o == reloaded);
+
+        let (text, reloaded) = write_and_load(&o, &WriteOptions{ precision: Some(2), ..WriteOptions::default() });
+        assert!(text.contains("v 0.10 0.12 -2.50\n") && text.contains("v 1.00 0.00 0.00\n"));
+        let rounded: Vec<f32> = o.pos_data.data.iter().map(|v| (v * 100.0).round() / 100.0).collect();
+        assert_eq!(reloaded.pos_data.data, rounded);
+        assert_eq!(reloaded.indicies, o.indicies);
+    }
+
+    #[test]
+    fn tangents_split_mirrored_seams() {
+        // Two quads side by side, the texture of the right one is the left one mirrored at x = 1
+        let src = "v 0 0 0\nv 1 0 0\nv 2 0 0\nv 0 1 0\nv 1 1 0\nv 2 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvt 1 1\nvn 0 0 1\n\
+                   f 1/1/1 2/2/1 5/4/1 4/3/1\nf 2/2/1 3/1/1 6/3/1 5/4/1\n";
+        let mut o = ObjData::<f32, u32>::new(Dimension::ThreeDim, Some(Dimension::TwoDim), Some(Dimension::ThreeDim));
+        o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap();
+        assert_eq!(o.pos_data.len(), 6);
+        o.generate_tangents().unwrap();
+        assert_eq!(o.pos_data.len(), 8);
+        let tangents = o.tangent_data.as_ref().unwrap();
+        for (i, v) in o.indicies.iter().enumerate() {
+            let expected = if i < 6 { [1.0, 0.0, 0.0, 1.0] } else { [-1.0, 0.0, 0.0, -1.0] };
+            assert_eq!(tangents.get_slice(*v as usize), &expected);
+        }
+    }
+
+    #[test]
+    fn parallel_load_matches_sequential() {
+        let src = generated_obj(100_000);
+        assert!(split_into_chunks(src.as_bytes(), 4).len() >= 4);
+        for mode in [ParseMode::Lenient, ParseMode::Strict] {
+            let mut sequential = ObjData::<f32, u32>::new(Dimension::ThreeDim, Some(Dimension::TwoDim), None);
+            let mut parallel = sequential.clone();
+            let a = sequential.load_from_bytes(src.as_bytes(), mode, None);
+            let b = parallel.load_parallel_from_bytes(src.as_bytes(), mode);
+            assert_eq!(a, b);
+            if mode == ParseMode::Strict {
+                assert!(a.is_err());
+            } else {
+                assert!(!a.unwrap().is_empty());
+                assert!(sequential.sub_meshes.len() > 100 && sequential.material_ranges.len() > 100);
+                assert!(sequential == parallel);
+            }
+        }
+    }
+
+    #[test]
+    fn optional_components_are_dropped() {
+        // What Blender and 3ds Max write
+        let src = "v 0 0 0 1\nv 1 0 0 1\nv 0 1 0\nvt 0 0 0\nvt 1 0\nvt 0 1 0\nf 1/1 2/2 3/3\n";
+        let mut o = new_obj(true, false);
+        assert_eq!(o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None), Ok(vec![]));
+        assert_eq!(o.pos_data.data, [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(o.tex_data.unwrap().data, [0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+
+        // Missing components are still an error
+        for src in ["v 0 0\n", "vt 0\n", "v 0 0 0 1 2\n", "vn 0 0\n"] {
+            let mut o = new_obj(true, true);
+            let e = o.load_from_bytes(src.as_bytes(), ParseMode::Strict, None).unwrap_err();
+            assert!(matches!(e.kind, ObjErrorKind::DimensionMismatch{ .. }), "{}", src);
+        }
+    }
+
+    #[test]
+    fn as_arrays_checks_dimension_and_length() {
+        let mut a: VertexAttribs<f32> = VertexAttribs::new(Dimension::TwoDim);
+        a.data = vec![1.0, 2.0, 3.0, 4.0];
+        assert_eq!(a.as_arrays::<2>(), Some(&[[1.0, 2.0], [3.0, 4.0]][..]));
+        assert_eq!(a.as_arrays::<3>(), None);
+        a.as_arrays_mut::<2>().unwrap()[1][0] = 5.0;
+        assert_eq!(a.data[2], 5.0);
+        a.data.push(6.0);
+        assert_eq!(a.as_arrays::<2>(), None);
+    }
 }
- 
\ No newline at end of file
@@ -0,0 +1,443 @@
+use std::convert::{TryFrom, TryInto};
+
+use crate::obj::{ObjData, ObjError, ObjErrorKind, ObjScalar, VertexAttribs};
+use crate::vector::{cross, dot, length, sub};
+
+/// Entries of the post-transform cache that is simulated if nothing else is asked for, a common size on current GPUs
+pub const DEFAULT_CACHE_SIZE: usize = 16;
+
+/// A FIFO vertex cache, a vertex is in it if fewer than size misses happened since it was added
+struct FifoCache {
+    size: usize,
+    /// When every vertex was last added, in misses
+    added: Vec<usize>,
+    time: usize,
+}
+
+impl FifoCache {
+    fn new(size: usize, vertex_count: usize) -> Self {
+        // Starting the clock after size makes every vertex a miss the first time
+        Self{ size, added: vec![0; vertex_count], time: size + 1 }
+    }
+
+    fn contains(&self, v: usize) -> bool {
+        self.time - self.added[v] <= self.size
+    }
+
+    /// Adds v if it isn't in the cache, true if that was a miss
+    fn touch(&mut self, v: usize) -> bool {
+        if self.contains(v) {
+            return false;
+        }
+        self.added[v] = self.time;
+        self.time += 1;
+        true
+    }
+}
+
+fn vertex_count_of(triangles: &[[usize; 3]]) -> usize {
+    triangles.iter().flatten().max().map_or(0, |m| m + 1)
+}
+
+/// Cache misses of every triangle, drawn in order with a FIFO cache of cache_size vertices
+fn misses_per_triangle(triangles: &[[usize; 3]], cache_size: usize) -> Vec<u8> {
+    let mut cache = FifoCache::new(cache_size, vertex_count_of(triangles));
+    triangles.iter().map(|t| t.iter().filter(|v| cache.touch(**v)).count() as u8).collect()
+}
+
+/// Average cache miss ratio with a FIFO cache, from 3 ( worst ) down to about 0.5, 0 without triangles
+pub fn acmr(triangles: &[[usize; 3]], cache_size: usize) -> f64 {
+    if triangles.is_empty() {
+        return 0.0;
+    }
+    let misses: usize = misses_per_triangle(triangles, cache_size).iter().map(|m| *m as usize).sum();
+    misses as f64 / triangles.len() as f64
+}
+
+/// Reorders triangles for the post-transform vertex cache with Tipsify ( Sander et al. 2007 )
+pub fn optimize_vertex_cache(triangles: &[[usize; 3]], cache_size: usize) -> Vec<[usize; 3]> {
+    if triangles.is_empty() {
+        return Vec::new();
+    }
+    let vertex_count = vertex_count_of(triangles);
+    // Triangles of every vertex, packed
+    let mut first: Vec<usize> = vec![0; vertex_count + 1];
+    for v in triangles.iter().flatten() {
+        first[v + 1] += 1;
+    }
+    for v in 0..vertex_count {
+        first[v + 1] += first[v];
+    }
+    let mut adjacent: Vec<usize> = vec![0; first[vertex_count]];
+    let mut filled = first.clone();
+    for (t, tri) in triangles.iter().enumerate() {
+        for v in tri {
+            adjacent[filled[*v]] = t;
+            filled[*v] += 1;
+        }
+    }
+    // Corners that haven't been drawn yet
+    let mut live: Vec<usize> = (0..vertex_count).map(|v| first[v + 1] - first[v]).collect();
+
+    let mut cache = FifoCache::new(cache_size, vertex_count);
+    let mut emitted: Vec<bool> = vec![false; triangles.len()];
+    let mut dead_ends: Vec<usize> = Vec::new();
+    let mut out: Vec<[usize; 3]> = Vec::with_capacity(triangles.len());
+    // Where the search for an unfinished vertex continues when there are no dead ends left
+    let mut cursor = 0;
+    let mut fanning = Some(0);
+
+    while let Some(f) = fanning {
+        let mut candidates: Vec<usize> = Vec::new();
+        for t in &adjacent[first[f]..first[f + 1]] {
+            if emitted[*t] {
+                continue;
+            }
+            emitted[*t] = true;
+            for v in triangles[*t] {
+                dead_ends.push(v);
+                candidates.push(v);
+                live[v] -= 1;
+                cache.touch(v);
+            }
+            out.push(triangles[*t]);
+        }
+
+        // The candidate that will still be in the cache after its remaining triangles, and has been there the longest
+        let mut best: Option<(usize, usize)> = None;
+        for v in candidates {
+            if live[v] == 0 {
+                continue;
+            }
+            let age = cache.time - cache.added[v];
+            let priority = if age + 2 * live[v] <= cache_size { age } else { 0 };
+            if best.is_none_or(|(p, _)| priority > p) {
+                best = Some((priority, v));
+            }
+        }
+        fanning = match best {
+            Some((_, v)) => Some(v),
+            None => {
+                // Go back along the vertices used recently, then on to ones that weren't used yet
+                let mut next = None;
+                while let Some(d) = dead_ends.pop() {
+                    if live[d] > 0 {
+                        next = Some(d);
+                        break;
+                    }
+                }
+                while next.is_none() && cursor < vertex_count {
+                    if live[cursor] > 0 {
+                        next = Some(cursor);
+                    }
+                    cursor += 1;
+                }
+                next
+            }
+        };
+    }
+    out
+}
+
+/// Where clusters start: where the order jumps, and once a cluster's ACMR is within threshold of its run
+fn clusters(triangles: &[[usize; 3]], cache_size: usize, threshold: f64) -> Vec<usize> {
+    let misses = misses_per_triangle(triangles, cache_size);
+    let mut hard: Vec<usize> = (0..triangles.len()).filter(|t| *t == 0 || misses[*t] == 3).collect();
+    hard.push(triangles.len());
+
+    let mut starts: Vec<usize> = Vec::new();
+    for c in hard.windows(2) {
+        let whole = acmr(&triangles[c[0]..c[1]], cache_size);
+        let mut start = c[0];
+        starts.push(start);
+        let mut cache = FifoCache::new(cache_size, vertex_count_of(&triangles[c[0]..c[1]]));
+        let mut cluster_misses = 0;
+        for (t, tri) in triangles.iter().enumerate().take(c[1] - 1).skip(c[0]) {
+            cluster_misses += tri.iter().filter(|v| cache.touch(**v)).count();
+            if cluster_misses as f64 / (t + 1 - start) as f64 <= whole * threshold {
+                start = t + 1;
+                starts.push(start);
+                cache = FifoCache::new(cache_size, cache.added.len());
+                cluster_misses = 0;
+            }
+        }
+    }
+    starts
+}
+
+/// Puts clusters facing out first, threshold ( at least 1, e.g. 1.05 ) is how much worse the ACMR of a split cluster may be
+pub fn optimize_overdraw(triangles: &[[usize; 3]], positions: &[[f64; 3]], cache_size: usize, threshold: f64) -> Vec<[usize; 3]> {
+    let mut starts = clusters(triangles, cache_size, threshold);
+    starts.push(triangles.len());
+
+    // Area weighted centroid and summed normal of the mesh and every cluster
+    let summarize = |tris: &[[usize; 3]]| -> ([f64; 3], [f64; 3]) {
+        let (mut centroid, mut normal, mut area) = ([0.0; 3], [0.0; 3], 0.0);
+        for t in tris {
+            let p = [positions[t[0]], positions[t[1]], positions[t[2]]];
+            let n = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+            let a = length(n);
+            for k in 0..3 {
+                centroid[k] += (p[0][k] + p[1][k] + p[2][k]) / 3.0 * a;
+                normal[k] += n[k];
+            }
+            area += a;
+        }
+        if area > 0.0 {
+            centroid = [centroid[0] / area, centroid[1] / area, centroid[2] / area];
+        }
+        (centroid, normal)
+    };
+    let (mesh_centroid, _) = summarize(triangles);
+    let mut order: Vec<(f64, usize)> = starts.windows(2).map(|c| {
+        let (centroid, normal) = summarize(&triangles[c[0]..c[1]]);
+        let l = length(normal);
+        let facing = if l > 0.0 { dot(sub(centroid, mesh_centroid), normal) / l } else { 0.0 };
+        (facing, c[0])
+    }).collect();
+    order.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mut out: Vec<[usize; 3]> = Vec::with_capacity(triangles.len());
+    for (_, start) in order {
+        let end = starts[starts.partition_point(|s| *s <= start)];
+        out.extend_from_slice(&triangles[start..end]);
+    }
+    out
+}
+
+/// New index of every vertex in the order indicies first use them, unused ones last
+pub fn vertex_fetch_remap(indicies: &[usize], vertex_count: usize) -> Vec<usize> {
+    let mut remap: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut next = 0;
+    for i in indicies {
+        if remap[*i].is_none() {
+            remap[*i] = Some(next);
+            next += 1;
+        }
+    }
+    remap.into_iter().map(|r| r.unwrap_or_else(|| { next += 1; next - 1 })).collect()
+}
+
+/// What `ObjData::optimize` does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizeOptions {
+    /// Vertices in the simulated post-transform cache
+    pub cache_size: usize,
+    /// Reorder clusters of triangles to reduce overdraw, see `optimize_overdraw`
+    pub overdraw_threshold: Option<f64>,
+    /// Store the vertices in the order the triangles use them, see `vertex_fetch_remap`
+    pub vertex_fetch: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self{ cache_size: DEFAULT_CACHE_SIZE, overdraw_threshold: Some(1.05), vertex_fetch: true }
+    }
+}
+
+/// How much `ObjData::optimize` helped, measured with its cache_size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizeReport {
+    pub acmr_before: f64,
+    pub acmr_after: f64,
+}
+
+/// Copies the elements of attribs to their new index
+fn remap_attribs<T: ObjScalar + Default>(attribs: &VertexAttribs<T>, remap: &[usize]) -> VertexAttribs<T> {
+    let mut out = VertexAttribs::new(attribs.get_elem_per_vert());
+    out.resize_to(attribs.len());
+    for (old, new) in remap.iter().enumerate() {
+        out.set_slice(*new, attribs.get_slice(old));
+    }
+    out
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy + TryInto<usize> + TryFrom<usize> {
+    /// ACMR of the triangles as they are now, see `acmr`
+    pub fn acmr(&self, cache_size: usize) -> Result<f64, ObjError> {
+        Ok(acmr(&self.triangles()?, cache_size))
+    }
+
+    /// Reorders the triangles inside their ranges for the vertex cache and, if asked to, for overdraw and vertex fetches
+    pub fn optimize(&mut self, options: &OptimizeOptions) -> Result<OptimizeReport, ObjError> {
+        let too_big = || ObjError::new(0, "", ObjErrorKind::IndicesTooBig);
+        if options.vertex_fetch {
+            self.check_attribute_counts()?;
+        }
+        let mut triangles = self.checked_triangles()?;
+        let vertex_count = self.pos_data.len();
+        let acmr_before = acmr(&triangles, options.cache_size);
+
+        // Every place a range starts or ends, the parts between them are optimised on their own
+        let mut bounds: Vec<usize> = vec![0, triangles.len()];
+        for r in self.sub_meshes.iter().map(|m| &m.range).chain(self.material_ranges.iter().map(|m| &m.range)) {
+            bounds.push(r.start / 3);
+            bounds.push(r.end / 3);
+        }
+        bounds.retain(|b| *b <= triangles.len());
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        let positions = self.positions_3d();
+        // The vertices of one part as 0.., to keep the work per part small
+        let mut local_of: Vec<Option<usize>> = vec![None; vertex_count];
+        for part in bounds.windows(2) {
+            let part = &mut triangles[part[0]..part[1]];
+            let mut global: Vec<usize> = Vec::new();
+            let local: Vec<[usize; 3]> = part.iter().map(|t| t.map(|v| {
+                *local_of[v].get_or_insert_with(|| { global.push(v); global.len() - 1 })
+            })).collect();
+            let mut optimized = optimize_vertex_cache(&local, options.cache_size);
+            if let Some(threshold) = options.overdraw_threshold {
+                let local_positions: Vec<[f64; 3]> = global.iter().map(|v| positions[*v]).collect();
+                optimized = optimize_overdraw(&optimized, &local_positions, options.cache_size, threshold);
+            }
+            for (dst, t) in part.iter_mut().zip(optimized) {
+                *dst = t.map(|v| global[v]);
+            }
+            for v in global {
+                local_of[v] = None;
+            }
+        }
+
+        if options.vertex_fetch {
+            let mut used: Vec<usize> = triangles.iter().flatten().copied().collect();
+            for i in self.line_indicies.iter().chain(&self.point_indicies) {
+                let i: usize = (*i).try_into().map_err(|_| too_big())?;
+                if i >= vertex_count {
+                    return Err(ObjError::new(0, "", ObjErrorKind::IndexOutOfRange{ attribute: "position", index: i.to_string(), loaded: vertex_count }));
+                }
+                used.push(i);
+            }
+            let remap = vertex_fetch_remap(&used, vertex_count);
+            self.pos_data = remap_attribs(&self.pos_data, &remap);
+            let others = self.tex_data.iter_mut().chain(self.norm_data.iter_mut()).chain(self.tangent_data.iter_mut()).chain(self.color_data.iter_mut());
+            for attribs in others {
+                *attribs = remap_attribs(attribs, &remap);
+            }
+            for t in &mut triangles {
+                *t = t.map(|v| remap[v]);
+            }
+            for i in self.line_indicies.iter_mut().chain(&mut self.point_indicies) {
+                let old: usize = (*i).try_into().map_err(|_| too_big())?;
+                *i = I::try_from(remap[old]).map_err(|_| too_big())?;
+            }
+        }
+
+        let acmr_after = acmr(&triangles, options.cache_size);
+        self.indicies = triangles.iter().flatten().map(|v| I::try_from(*v).map_err(|_| too_big())).collect::<Result<_, _>>()?;
+        Ok(OptimizeReport{ acmr_before, acmr_after })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Range;
+    use crate::obj::{Dimension, SubMesh};
+
+    /// Positions and the triangles of a size by size grid of quads, in a reproducible shuffled order
+    fn shuffled_grid(size: usize) -> (Vec<[f64; 3]>, Vec<[usize; 3]>) {
+        let positions: Vec<[f64; 3]> = (0..(size + 1) * (size + 1)).map(|i| [(i % (size + 1)) as f64, (i / (size + 1)) as f64, 0.0]).collect();
+        let mut triangles: Vec<[usize; 3]> = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let v = y * (size + 1) + x;
+                triangles.push([v, v + 1, v + size + 2]);
+                triangles.push([v, v + size + 2, v + size + 1]);
+            }
+        }
+        let mut seed: u64 = 3;
+        for i in (1..triangles.len()).rev() {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            triangles.swap(i, (seed >> 33) as usize % (i + 1));
+        }
+        (positions, triangles)
+    }
+
+    fn sorted(mut triangles: Vec<[usize; 3]>) -> Vec<[usize; 3]> {
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn vertex_cache_order_is_a_permutation_with_fewer_misses() {
+        assert!(optimize_vertex_cache(&[], DEFAULT_CACHE_SIZE).is_empty());
+        let (_, triangles) = shuffled_grid(40);
+        let optimized = optimize_vertex_cache(&triangles, DEFAULT_CACHE_SIZE);
+        assert_eq!(sorted(optimized.clone()), sorted(triangles.clone()));
+        let (before, after) = (acmr(&triangles, DEFAULT_CACHE_SIZE), acmr(&optimized, DEFAULT_CACHE_SIZE));
+        assert!(before > 2.0 && after < 0.9, "{} -> {}", before, after);
+    }
+
+    #[test]
+    fn overdraw_order_keeps_every_triangle() {
+        let (positions, triangles) = shuffled_grid(30);
+        let cache_optimized = optimize_vertex_cache(&triangles, DEFAULT_CACHE_SIZE);
+        for threshold in [1.0, 1.05, 2.0] {
+            let optimized = optimize_overdraw(&cache_optimized, &positions, DEFAULT_CACHE_SIZE, threshold);
+            assert_eq!(sorted(optimized), sorted(triangles.clone()));
+        }
+    }
+
+    #[test]
+    fn vertex_fetch_remap_numbers_vertices_by_first_use() {
+        assert_eq!(vertex_fetch_remap(&[2, 0, 2, 3], 5), [1, 3, 0, 2, 4]);
+
+        // The mesh stays the same, only the triangles in each sub-mesh are reordered
+        let (positions, triangles) = shuffled_grid(12);
+        let mut o: ObjData<f32, u32> = ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), None);
+        o.pos_data.data = positions.iter().flat_map(|p| [p[0] as f32, p[1] as f32, 0.0]).collect();
+        o.tex_data.as_mut().unwrap().data = positions.iter().flat_map(|p| [p[0] as f32 / 12.0, p[1] as f32 / 12.0]).collect();
+        o.indicies = triangles.iter().flatten().map(|v| *v as u32).collect();
+        let half = o.indicies.len() / 2;
+        o.sub_meshes = vec![
+            SubMesh{ object: "a".to_owned(), group: String::new(), material_name: None, material: None, range: 0..half },
+            SubMesh{ object: "b".to_owned(), group: String::new(), material_name: None, material: None, range: half..o.indicies.len() },
+        ];
+        let corners = |o: &ObjData<f32, u32>, range: Range<usize>| -> Vec<[[u32; 4]; 3]> {
+            let mut tris: Vec<[[u32; 4]; 3]> = o.indicies[range].chunks_exact(3).map(|t| {
+                let corner = |i: u32| {
+                    let (p, t) = (o.pos_data.get_slice(i as usize), o.tex_data.as_ref().unwrap().get_slice(i as usize));
+                    [p[0].to_bits(), p[1].to_bits(), t[0].to_bits(), t[1].to_bits()]
+                };
+                [corner(t[0]), corner(t[1]), corner(t[2])]
+            }).collect();
+            tris.sort_unstable();
+            tris
+        };
+        let before = o.clone();
+        let report = o.optimize(&OptimizeOptions::default()).unwrap();
+        assert!(report.acmr_after < report.acmr_before);
+        for range in [0..half, half..o.indicies.len()] {
+            assert_eq!(corners(&o, range.clone()), corners(&before, range));
+        }
+        // Vertices are stored in the order they are first used
+        let mut first_use: Vec<u32> = Vec::new();
+        for i in &o.indicies {
+            if !first_use.contains(i) {
+                first_use.push(*i);
+            }
+        }
+        assert_eq!(first_use, (0..o.pos_data.len() as u32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn attributes_must_match_the_positions() {
+        let mut o: ObjData<f32, u32> = ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), None);
+        o.pos_data.data = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        o.tex_data.as_mut().unwrap().data = vec![0.0, 0.0, 1.0, 0.0];
+        o.indicies = vec![2, 1, 0];
+        let before = o.clone();
+        let e = o.optimize(&OptimizeOptions::default()).unwrap_err();
+        assert_eq!(e.kind, ObjErrorKind::AttributeCountMismatch{ attribute: "texture coordinates", expected: 3, found: 2 });
+        assert!(o == before);
+
+        o.optimize(&OptimizeOptions{ vertex_fetch: false, ..Default::default() }).unwrap();
+        o.tex_data.as_mut().unwrap().data.extend_from_slice(&[0.0, 1.0]);
+        o.optimize(&OptimizeOptions::default()).unwrap();
+        assert_eq!(o.indicies, vec![0, 1, 2]);
+        assert_eq!(o.tex_data.unwrap().data, vec![0.0, 1.0, 1.0, 0.0, 0.0, 0.0]);
+    }
+}
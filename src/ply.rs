@@ -0,0 +1,579 @@
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{self, BufRead},
+    path::Path,
+};
+
+use crate::obj::{Dimension, ObjData, ObjError, ObjErrorKind, ObjScalar, ParseMode, SubMesh, VertexAttribs};
+use crate::triangulate::{self, TriangulationError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// The type of a property ( or of the count and items of a list property )
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PlyType {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "char" | "int8" => Some(PlyType::Int8),
+            "uchar" | "uint8" => Some(PlyType::UInt8),
+            "short" | "int16" => Some(PlyType::Int16),
+            "ushort" | "uint16" => Some(PlyType::UInt16),
+            "int" | "int32" => Some(PlyType::Int32),
+            "uint" | "uint32" => Some(PlyType::UInt32),
+            "float" | "float32" => Some(PlyType::Float32),
+            "double" | "float64" => Some(PlyType::Float64),
+            _ => None
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            PlyType::Int8 | PlyType::UInt8 => 1,
+            PlyType::Int16 | PlyType::UInt16 => 2,
+            PlyType::Int32 | PlyType::UInt32 | PlyType::Float32 => 4,
+            PlyType::Float64 => 8,
+        }
+    }
+
+    /// The biggest value of integer types, colours stored as integers are divided by it
+    fn max_value(self) -> Option<f64> {
+        match self {
+            PlyType::Int8 => Some(i8::MAX as f64),
+            PlyType::UInt8 => Some(u8::MAX as f64),
+            PlyType::Int16 => Some(i16::MAX as f64),
+            PlyType::UInt16 => Some(u16::MAX as f64),
+            PlyType::Int32 => Some(i32::MAX as f64),
+            PlyType::UInt32 => Some(u32::MAX as f64),
+            PlyType::Float32 | PlyType::Float64 => None,
+        }
+    }
+
+    fn read(self, b: &[u8], big_endian: bool) -> f64 {
+        macro_rules! read {
+            ($t: ty, $n: expr) => {{
+                let mut a = [0u8; $n];
+                a.copy_from_slice(b);
+                (if big_endian { <$t>::from_be_bytes(a) } else { <$t>::from_le_bytes(a) }) as f64
+            }};
+        }
+        match self {
+            PlyType::Int8 => b[0] as i8 as f64,
+            PlyType::UInt8 => b[0] as f64,
+            PlyType::Int16 => read!(i16, 2),
+            PlyType::UInt16 => read!(u16, 2),
+            PlyType::Int32 => read!(i32, 4),
+            PlyType::UInt32 => read!(u32, 4),
+            PlyType::Float32 => read!(f32, 4),
+            PlyType::Float64 => read!(f64, 8),
+        }
+    }
+}
+
+/// Values of one property, one per element
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlyValues {
+    Scalar(Vec<f64>),
+    List(Vec<Vec<f64>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlyProperty {
+    pub name: String,
+    pub kind: PlyType,
+    pub values: PlyValues,
+}
+
+/// An element of a PLY file with the properties that didn't go into `ObjData`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlyElement {
+    pub name: String,
+    pub count: usize,
+    pub properties: Vec<PlyProperty>,
+}
+
+struct PropertyDef {
+    name: String,
+    kind: PlyType,
+    /// The type of the count for list properties
+    list: Option<PlyType>,
+}
+
+struct ElementDef {
+    name: String,
+    count: usize,
+    properties: Vec<PropertyDef>,
+}
+
+/// Where the value of a property goes
+#[derive(Clone, Copy)]
+enum Target {
+    Pos(usize),
+    Tex(usize),
+    Norm(usize),
+    Color(usize),
+    Indices,
+    /// Index into the properties of the `PlyElement`
+    Extra(usize),
+}
+
+/// Reads the lines up to end_header, line_no is the number of the last line read and read the number of bytes
+fn parse_header<R: BufRead>(r: &mut R, line_no: &mut usize, read: &mut u64) -> Result<(PlyFormat, Vec<ElementDef>), ObjError> {
+    let mut next_line = |line_no: &mut usize| -> Result<String, ObjError> {
+        let mut line = String::new();
+        *line_no += 1;
+        match r.read_line(&mut line) {
+            Ok(0) => Err(ObjError::new(*line_no, "", ObjErrorKind::InvalidHeader("missing end_header".to_owned()))),
+            Ok(n) => {
+                *read += n as u64;
+                Ok(line.trim_end_matches(['\n', '\r']).to_owned())
+            },
+            Err(e) => Err(ObjError::new(*line_no, "", ObjErrorKind::Io(e.kind())))
+        }
+    };
+    if next_line(line_no)?.trim() != "ply" {
+        return Err(ObjError::new(*line_no, "", ObjErrorKind::InvalidHeader("not a ply file".to_owned())));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<ElementDef> = Vec::new();
+    loop {
+        let line = next_line(line_no)?;
+        let bad = |msg: &str| ObjError::new(*line_no, &line, ObjErrorKind::InvalidHeader(msg.to_owned()));
+        let parse_type = |s: &str| PlyType::parse(s).ok_or_else(|| bad(&format!("unknown type '{}'", s)));
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => {},
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] => {},
+            ["format", f, version] => {
+                if *version != "1.0" {
+                    return Err(bad(&format!("unsupported version '{}'", version)));
+                }
+                format = Some(match *f {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return Err(bad(&format!("unknown format '{}'", f)))
+                });
+            },
+            ["element", name, count] => {
+                let count = count.parse().map_err(|_| ObjError::new(*line_no, &line, ObjErrorKind::InvalidNumber((*count).to_owned())))?;
+                elements.push(ElementDef{ name: (*name).to_owned(), count, properties: Vec::new() });
+            },
+            ["property", "list", count_type, item_type, name] => {
+                let def = PropertyDef{ name: (*name).to_owned(), kind: parse_type(item_type)?, list: Some(parse_type(count_type)?) };
+                elements.last_mut().ok_or_else(|| bad("property before element"))?.properties.push(def);
+            },
+            ["property", kind, name] => {
+                let def = PropertyDef{ name: (*name).to_owned(), kind: parse_type(kind)?, list: None };
+                elements.last_mut().ok_or_else(|| bad("property before element"))?.properties.push(def);
+            },
+            _ => return Err(bad("unknown header line"))
+        }
+    }
+    let format = format.ok_or_else(|| ObjError::new(*line_no, "", ObjErrorKind::InvalidHeader("missing format".to_owned())))?;
+    Ok((format, elements))
+}
+
+/// Reads the values of one element after another, from text lines or binary data
+struct RowReader<R> {
+    r: R,
+    format: PlyFormat,
+    line_no: usize,
+    line: String,
+    tokens: Vec<String>,
+    next_token: usize,
+    buf: [u8; 8],
+    /// Bytes after the header that weren't read yet, if the size of the file is known
+    remaining: Option<u64>,
+}
+
+impl<R: BufRead> RowReader<R> {
+    /// For ascii files reads the next non-empty line
+    fn start_row(&mut self) -> Result<(), ObjError> {
+        if self.format != PlyFormat::Ascii {
+            return Ok(());
+        }
+        loop {
+            self.line.clear();
+            self.line_no += 1;
+            let n = self.r.read_line(&mut self.line).map_err(|e| ObjError::new(self.line_no, "", ObjErrorKind::Io(e.kind())))?;
+            if n == 0 {
+                return Err(ObjError::new(self.line_no, "", ObjErrorKind::UnexpectedEof));
+            }
+            if !self.line.trim().is_empty() {
+                break;
+            }
+        }
+        self.tokens = self.line.split_whitespace().map(|t| t.to_owned()).collect();
+        self.next_token = 0;
+        Ok(())
+    }
+
+    /// Errors for the current row, Err is for errors that stop loading
+    fn value(&mut self, kind: PlyType) -> Result<Result<f64, ObjErrorKind>, ObjErrorKind> {
+        match self.format {
+            PlyFormat::Ascii => {
+                let t = match self.tokens.get(self.next_token) {
+                    Some(t) => t,
+                    None => return Ok(Err(ObjErrorKind::WrongArgumentCount(self.tokens.len())))
+                };
+                self.next_token += 1;
+                Ok(t.parse().map_err(|_| ObjErrorKind::InvalidNumber(t.clone())))
+            },
+            _ => {
+                let size = kind.size();
+                self.r.read_exact(&mut self.buf[..size]).map_err(|e| match e.kind() {
+                    io::ErrorKind::UnexpectedEof => ObjErrorKind::UnexpectedEof,
+                    k => ObjErrorKind::Io(k)
+                })?;
+                self.remaining = self.remaining.map(|r| r.saturating_sub(size as u64));
+                Ok(Ok(kind.read(&self.buf[..size], self.format == PlyFormat::BinaryBigEndian)))
+            }
+        }
+    }
+
+    /// Whether the current ascii row had values that no property used
+    fn row_has_leftovers(&self) -> bool {
+        self.format == PlyFormat::Ascii && self.next_token < self.tokens.len()
+    }
+
+    /// Whether the rest of the input can hold count values of kind ( as far as that is known )
+    fn has_room_for(&self, count: usize, kind: PlyType) -> bool {
+        match self.format {
+            PlyFormat::Ascii => count <= self.tokens.len() - self.next_token,
+            _ => self.remaining.is_none_or(|r| count as u64 <= r / kind.size() as u64)
+        }
+    }
+
+    /// How many rows of at least row_size bytes to reserve room for, count if it fits in the rest of the input
+    fn reserve_rows(&self, count: usize, row_size: usize) -> usize {
+        match self.remaining {
+            Some(r) => count.min((r / row_size.max(1) as u64).min(usize::MAX as u64) as usize),
+            None => 0
+        }
+    }
+
+    /// Where errors in the current row are reported: the line for ascii files, the element and row otherwise
+    fn error(&self, element: &str, row: usize, kind: ObjErrorKind) -> ObjError {
+        match self.format {
+            PlyFormat::Ascii => ObjError::new(self.line_no, self.line.trim_end(), kind),
+            _ => ObjError::new(0, &format!("{} {}", element, row), kind)
+        }
+    }
+}
+
+fn vertex_target(name: &str, pos_dim: usize, tex_dim: usize, norm_dim: usize) -> Option<Target> {
+    let t = match name {
+        "x" => Target::Pos(0),
+        "y" => Target::Pos(1),
+        "z" => Target::Pos(2),
+        "w" => Target::Pos(3),
+        "u" | "s" | "texture_u" | "texture_s" => Target::Tex(0),
+        "v" | "t" | "texture_v" | "texture_t" => Target::Tex(1),
+        "nx" => Target::Norm(0),
+        "ny" => Target::Norm(1),
+        "nz" => Target::Norm(2),
+        "red" | "diffuse_red" => Target::Color(0),
+        "green" | "diffuse_green" => Target::Color(1),
+        "blue" | "diffuse_blue" => Target::Color(2),
+        "alpha" | "diffuse_alpha" => Target::Color(3),
+        _ => return None
+    };
+    // Components that the requested dimensions have no room for are kept as extra properties
+    match t {
+        Target::Pos(k) if k >= pos_dim => None,
+        Target::Tex(k) if k >= tex_dim => None,
+        Target::Norm(k) if k >= norm_dim => None,
+        _ => Some(t)
+    }
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy + TryFrom<usize>{
+    /// Replaces the mesh with a PLY file, returns the properties that weren't used and in lenient mode the skipped rows
+    pub fn load_ply<R: BufRead>(&mut self, r: R, mode: ParseMode) -> Result<(Vec<PlyElement>, Vec<ObjError>), ObjError>{
+        self.load_ply_sized(r, mode, None)
+    }
+
+    /// Loads the PLY file at path, see `load_ply`
+    pub fn load_ply_from_path(&mut self, path: &Path, mode: ParseMode) -> Result<(Vec<PlyElement>, Vec<ObjError>), ObjError>{
+        let open_err = |e: io::Error| ObjError::new(0, &path.to_string_lossy(), ObjErrorKind::Io(e.kind()));
+        let f = File::open(path).map_err(open_err)?;
+        let len = f.metadata().map_err(open_err)?.len();
+        self.load_ply_sized(io::BufReader::new(f), mode, Some(len))
+    }
+
+    /// len is the size of the whole file if it is known, it bounds what is reserved up front
+    fn load_ply_sized<R: BufRead>(&mut self, mut r: R, mode: ParseMode, len: Option<u64>) -> Result<(Vec<PlyElement>, Vec<ObjError>), ObjError>{
+        let mut warnings: Vec<ObjError> = Vec::new();
+        let mut report = |e: ObjError| mode.report(e, &mut warnings);
+
+        let mut line_no = 0;
+        let mut header_len = 0;
+        let (format, defs) = parse_header(&mut r, &mut line_no, &mut header_len)?;
+        let remaining = len.map(|l| l.saturating_sub(header_len));
+        let mut rows = RowReader{ r, format, line_no, line: String::new(), tokens: Vec::new(), next_token: 0, buf: [0; 8], remaining };
+
+        let pos_dim = self.pos_data.get_elem_per_vert() as usize;
+        let tex_dim = self.tex_data.as_ref().map_or(0, |t| t.get_elem_per_vert() as usize);
+        let norm_dim = self.norm_data.as_ref().map_or(0, |n| n.get_elem_per_vert() as usize);
+
+        let mut pos: Vec<f64> = Vec::new();
+        let mut tex: Vec<f64> = Vec::new();
+        let mut norm: Vec<f64> = Vec::new();
+        let mut color: Vec<f64> = Vec::new();
+        let mut color_dim = 0;
+        let mut color_scale = [1.0; 4];
+        let mut vertex_count = 0;
+        // Corners of every face, with the line ( 0 for binary files ) and row it was read from for errors
+        let mut faces: Vec<(Vec<usize>, usize, usize)> = Vec::new();
+        let mut elements: Vec<PlyElement> = Vec::new();
+
+        for def in &defs {
+            let is_vertex = def.name == "vertex";
+            let is_face = def.name == "face";
+            let mut element = PlyElement{ name: def.name.clone(), count: def.count, properties: Vec::new() };
+            // The smallest a row can be, a value and a separator per property for ascii files
+            let row_size = match format {
+                PlyFormat::Ascii => 2 * def.properties.len(),
+                _ => def.properties.iter().map(|p| p.list.unwrap_or(p.kind).size()).sum()
+            };
+            let reserved = rows.reserve_rows(def.count, row_size);
+            if format != PlyFormat::Ascii && def.count > 0 && rows.remaining.is_some_and(|r| (def.count as u64).saturating_mul(row_size as u64) > r) {
+                return Err(rows.error(&def.name, 0, ObjErrorKind::UnexpectedEof));
+            }
+            let targets: Vec<Target> = def.properties.iter().map(|p| {
+                let known = match (p.list, is_vertex, is_face) {
+                    (None, true, _) => vertex_target(&p.name, pos_dim, tex_dim, norm_dim),
+                    (Some(_), _, true) if p.name == "vertex_indices" || p.name == "vertex_index" => Some(Target::Indices),
+                    _ => None
+                };
+                known.unwrap_or_else(|| {
+                    let values = if p.list.is_some() { PlyValues::List(Vec::with_capacity(reserved)) } else { PlyValues::Scalar(Vec::with_capacity(reserved)) };
+                    element.properties.push(PlyProperty{ name: p.name.clone(), kind: p.kind, values });
+                    Target::Extra(element.properties.len() - 1)
+                })
+            }).collect();
+            if is_vertex {
+                for (p, t) in def.properties.iter().zip(&targets) {
+                    if let Target::Color(k) = t {
+                        color_dim = color_dim.max(if *k == 3 { 4 } else { 3 });
+                        color_scale[*k] = p.kind.max_value().unwrap_or(1.0);
+                    }
+                }
+                vertex_count = def.count;
+            }
+
+            for row in 0..def.count {
+                rows.start_row()?;
+                let mut row_err: Option<ObjErrorKind> = None;
+                let mut vals = [[0.0f64; 4]; 4];
+                // Homogeneous w and colour channels are 1 if the file doesn't have them
+                vals[0][3] = 1.0;
+                vals[3] = [1.0; 4];
+                let mut corners: Vec<usize> = Vec::new();
+                for (p, t) in def.properties.iter().zip(&targets) {
+                    let read = |rows: &mut RowReader<R>, row_err: &mut Option<ObjErrorKind>, kind: PlyType| -> Result<f64, ObjError> {
+                        match rows.value(kind) {
+                            Ok(Ok(v)) => Ok(v),
+                            Ok(Err(e)) => { row_err.get_or_insert(e); Ok(0.0) },
+                            Err(e) => Err(rows.error(&def.name, row, e))
+                        }
+                    };
+                    let list: Option<Vec<f64>> = match p.list {
+                        Some(count_type) => {
+                            let count = read(&mut rows, &mut row_err, count_type)?;
+                            let valid = count >= 0.0 && count.fract() == 0.0 && rows.has_room_for(count as usize, p.kind);
+                            let count = match (valid, format) {
+                                (true, _) => count as usize,
+                                // The rest of a binary file can't be read without knowing where the row ends
+                                (false, PlyFormat::Ascii) => { row_err.get_or_insert(ObjErrorKind::InvalidNumber(count.to_string())); 0 },
+                                (false, _) => return Err(rows.error(&def.name, row, ObjErrorKind::InvalidNumber(count.to_string())))
+                            };
+                            let mut items = Vec::new();
+                            for _ in 0..count {
+                                items.push(read(&mut rows, &mut row_err, p.kind)?);
+                            }
+                            Some(items)
+                        },
+                        None => None
+                    };
+                    let scalar = match list { Some(_) => 0.0, None => read(&mut rows, &mut row_err, p.kind)? };
+                    match *t {
+                        Target::Pos(k) => vals[0][k] = scalar,
+                        Target::Tex(k) => vals[1][k] = scalar,
+                        Target::Norm(k) => vals[2][k] = scalar,
+                        Target::Color(k) => vals[3][k] = scalar / color_scale[k],
+                        Target::Indices => for i in list.unwrap_or_default() {
+                            if i < 0.0 || i.fract() != 0.0 {
+                                row_err.get_or_insert(ObjErrorKind::InvalidIndex(i.to_string()));
+                            }
+                            corners.push(i as usize);
+                        },
+                        Target::Extra(e) => match (&mut element.properties[e].values, list) {
+                            (PlyValues::List(l), Some(items)) => l.push(items),
+                            (PlyValues::Scalar(s), None) => s.push(scalar),
+                            _ => unreachable!()
+                        }
+                    }
+                }
+                if row_err.is_none() && rows.row_has_leftovers() {
+                    row_err = Some(ObjErrorKind::WrongArgumentCount(rows.tokens.len()));
+                }
+                if let Some(kind) = row_err {
+                    // Bad vertices are kept with 0 for the bad values, like in obj files
+                    report(rows.error(&def.name, row, kind))?;
+                    if is_face {
+                        continue;
+                    }
+                }
+                if is_vertex {
+                    pos.extend_from_slice(&vals[0][..pos_dim]);
+                    tex.extend_from_slice(&vals[1][..tex_dim]);
+                    norm.extend_from_slice(&vals[2][..norm_dim]);
+                    color.extend_from_slice(&vals[3][..color_dim]);
+                } else if is_face {
+                    faces.push((corners, if format == PlyFormat::Ascii { rows.line_no } else { 0 }, row));
+                }
+            }
+            elements.push(element);
+        }
+
+        // Faces can come before the vertices, so they are only triangulated once everything is read
+        let mut triangles: Vec<usize> = Vec::with_capacity(faces.len() * 3);
+        for (corners, line, row) in faces {
+            let fail = |kind| ObjError::new(line, &format!("face {}", row), kind);
+            if let Some(bad) = corners.iter().find(|c| **c >= vertex_count) {
+                report(fail(ObjErrorKind::IndexOutOfRange{ attribute: "vertex", index: bad.to_string(), loaded: vertex_count }))?;
+                continue;
+            }
+            if corners.len() < 3 {
+                report(fail(ObjErrorKind::TooFewVertices(corners.len())))?;
+                continue;
+            }
+            if pos_dim == 1 {
+                for i in 1..corners.len() - 1 {
+                    triangles.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                }
+                continue;
+            }
+            let points: Vec<[f64; 3]> = corners.iter().map(|c| {
+                let p = &pos[c * pos_dim..c * pos_dim + pos_dim];
+                [p[0], p[1], if pos_dim > 2 { p[2] } else { 0.0 }]
+            }).collect();
+            match triangulate::triangulate(&points) {
+                Ok(tris) => for t in tris {
+                    triangles.extend_from_slice(&[corners[t[0]], corners[t[1]], corners[t[2]]]);
+                },
+                Err(TriangulationError::Degenerate) => report(fail(ObjErrorKind::DegeneratePolygon))?,
+                Err(TriangulationError::SelfIntersecting) => report(fail(ObjErrorKind::SelfIntersectingPolygon))?,
+            }
+        }
+
+        let attribs = |data: &[f64], dim: Dimension| {
+            let mut a = VertexAttribs::new(dim);
+            a.data = data.iter().map(|v| T::from_f64(*v)).collect();
+            a
+        };
+        let mut indicies: Vec<I> = Vec::with_capacity(triangles.len());
+        for i in triangles {
+            indicies.push(I::try_from(i).map_err(|_| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))?);
+        }
+        self.pos_data = attribs(&pos, self.pos_data.get_elem_per_vert());
+        self.tex_data = self.tex_data.as_ref().map(|t| attribs(&tex, t.get_elem_per_vert()));
+        self.norm_data = self.norm_data.as_ref().map(|n| attribs(&norm, n.get_elem_per_vert()));
+        self.color_data = match color_dim {
+            3 => Some(attribs(&color, Dimension::ThreeDim)),
+            4 => Some(attribs(&color, Dimension::FourDim)),
+            _ => None
+        };
+        self.tangent_data = None;
+        self.mtl_libs.clear();
+        self.materials.clear();
+        self.material_ranges.clear();
+        self.sub_meshes.clear();
+        if !indicies.is_empty() {
+            self.sub_meshes.push(SubMesh{ object: String::new(), group: String::new(), material_name: None, material: None, range: 0..indicies.len() });
+        }
+        self.indicies = indicies;
+        self.line_indicies.clear();
+        self.point_indicies.clear();
+        Ok((elements, warnings))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::Dimension;
+
+    fn new_obj() -> ObjData<f32, u32> {
+        ObjData::new(Dimension::ThreeDim, None, None)
+    }
+
+    fn binary_header(elements: &str) -> Vec<u8> {
+        format!("ply\nformat binary_little_endian 1.0\n{}end_header\n", elements).into_bytes()
+    }
+
+    #[test]
+    fn huge_counts_fail_without_reserving() {
+        let mut b = binary_header("element vertex 4000000000\nproperty float x\nproperty float y\nproperty float z\n");
+        b.extend_from_slice(&[0; 20]);
+        let len = b.len() as u64;
+        let e = new_obj().load_ply(&b[..], ParseMode::Lenient).unwrap_err();
+        assert_eq!(e.kind, ObjErrorKind::UnexpectedEof);
+        assert_eq!(e.to_string(), "vertex 1: unexpected end of file");
+        let e = new_obj().load_ply_sized(&b[..], ParseMode::Lenient, Some(len)).unwrap_err();
+        assert_eq!(e.to_string(), "vertex 0: unexpected end of file");
+
+        let mut b = binary_header("element vertex 0\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uint uint vertex_indices\n");
+        b.extend_from_slice(&u32::MAX.to_le_bytes());
+        b.extend_from_slice(&[0; 12]);
+        let len = b.len() as u64;
+        let e = new_obj().load_ply_sized(&b[..], ParseMode::Lenient, Some(len)).unwrap_err();
+        assert_eq!(e.to_string(), format!("face 0: '{}' is not a valid number", u32::MAX));
+        let e = new_obj().load_ply(&b[..], ParseMode::Lenient).unwrap_err();
+        assert_eq!(e.to_string(), "face 0: unexpected end of file");
+    }
+
+    #[test]
+    fn ascii_list_counts_must_fit_the_line() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 2\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n200 0 1 2\n3 0 1 2\n";
+        let mut o = new_obj();
+        let (_, warnings) = o.load_ply(ply.as_bytes(), ParseMode::Lenient).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].line, 13);
+        assert_eq!(warnings[0].kind, ObjErrorKind::InvalidNumber("200".to_owned()));
+        assert_eq!(o.indicies, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn triangles_are_validated_like_polygons() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 4\nproperty float x\nproperty float y\nproperty float z\nelement face 3\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n2 0 0\n3 0 1 2\n3 0 1 3\n3 0 0 2\n";
+        let mut o = new_obj();
+        let (_, warnings) = o.load_ply(ply.as_bytes(), ParseMode::Lenient).unwrap();
+        let kinds: Vec<_> = warnings.iter().map(|w| (w.line, w.kind.clone())).collect();
+        assert_eq!(kinds, vec![(15, ObjErrorKind::DegeneratePolygon), (16, ObjErrorKind::DegeneratePolygon)]);
+        assert_eq!(o.indicies, vec![0, 1, 2]);
+        let e = new_obj().load_ply(ply.as_bytes(), ParseMode::Strict).unwrap_err();
+        assert_eq!((e.line, e.kind), (15, ObjErrorKind::DegeneratePolygon));
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    convert::TryFrom,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use glam::Mat4;
+use gltf::{buffer, mesh::Mode, Gltf};
+
+use crate::mtl::Material;
+use crate::obj::{Dimension, MaterialRange, ObjData, ObjScalar, SubMesh, VertexAttribs, WeldTolerance};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneError {
+    Io(io::ErrorKind),
+    /// The file isn't valid glTF, or a buffer it needs couldn't be loaded
+    Gltf(String),
+    /// A primitive that isn't made of triangles was skipped
+    UnsupportedPrimitive { mesh: usize, primitive: usize, mode: String },
+    /// A primitive whose positions couldn't be read was skipped
+    MissingPositions { mesh: usize, primitive: usize },
+    IndicesTooBig,
+    /// The node is its own ancestor, or the child of more than one node
+    NodeCycle(usize),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(k) => write!(f, "io error: {:?}", k),
+            SceneError::Gltf(s) => write!(f, "invalid glTF: {}", s),
+            SceneError::UnsupportedPrimitive { mesh, primitive, mode } => write!(f, "primitive {} of mesh {} has unsupported mode {}", primitive, mesh, mode),
+            SceneError::MissingPositions { mesh, primitive } => write!(f, "primitive {} of mesh {} has no readable positions", primitive, mesh),
+            SceneError::IndicesTooBig => write!(f, "the indices don't fit into the index type"),
+            SceneError::NodeCycle(n) => write!(f, "node {} is reached more than once from the roots", n),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<gltf::Error> for SceneError {
+    fn from(e: gltf::Error) -> Self {
+        match e {
+            gltf::Error::Io(e) => SceneError::Io(e.kind()),
+            e => SceneError::Gltf(e.to_string())
+        }
+    }
+}
+
+impl From<SceneError> for io::Error {
+    fn from(e: SceneError) -> Self {
+        match e {
+            SceneError::Io(k) => io::Error::new(k, e.to_string()),
+            _ => io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fully transparent below `PbrMaterial::alpha_cutoff`, opaque otherwise
+    Mask,
+    Blend,
+}
+
+/// A metallic-roughness material, textures are indices into `Scene::images`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PbrMaterial {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Roughness in green, metallic in blue
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub occlusion_texture: Option<usize>,
+    pub emissive: [f32; 3],
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    /// Only used with `AlphaMode::Mask`
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+/// Where the data of an image is, images aren't decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageSource {
+    /// An external file, already joined with the directory of the glTF file
+    File(PathBuf),
+    /// Stored in a buffer or a data URI
+    Embedded { mime_type: String, data: Vec<u8> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneNode {
+    pub name: String,
+    /// Relative to the parent node
+    pub transform: Mat4,
+    /// Index into `Scene::meshes`
+    pub mesh: Option<usize>,
+    /// Indices into `Scene::nodes`
+    pub children: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scene<T, I> {
+    /// One per glTF mesh with a sub-mesh per primitive, the `ObjData` materials are phong versions of `Scene::materials`
+    pub meshes: Vec<ObjData<T, I>>,
+    pub nodes: Vec<SceneNode>,
+    /// Nodes of the default scene ( or the first one ) that have no parent
+    pub roots: Vec<usize>,
+    pub materials: Vec<PbrMaterial>,
+    pub images: Vec<ImageSource>,
+}
+
+fn image_source(image: &gltf::Image, buffers: &[buffer::Data], base_dir: &Path) -> Result<ImageSource, SceneError> {
+    match image.source() {
+        gltf::image::Source::View{ view, mime_type } => {
+            let data = &buffers[view.buffer().index()];
+            Ok(ImageSource::Embedded{ mime_type: mime_type.to_owned(), data: data[view.offset()..view.offset() + view.length()].to_vec() })
+        },
+        gltf::image::Source::Uri{ uri, mime_type } => {
+            if let Some(rest) = uri.strip_prefix("data:") {
+                // glTF only allows base64 data URIs, which gltf decodes like buffers
+                let (header, payload) = rest.split_once(";base64,").ok_or_else(|| SceneError::Gltf(format!("data URI '{}' isn't base64", uri)))?;
+                let mut data = buffer::Data::from_source(buffer::Source::Uri(uri), None)?.0;
+                // Buffers are padded to 4 bytes, images shouldn't be
+                let padding = payload.bytes().rev().take_while(|b| *b == b'=').count();
+                data.truncate((payload.len() / 4 * 3).saturating_sub(padding));
+                let mime_type = mime_type.unwrap_or(header).to_owned();
+                Ok(ImageSource::Embedded{ mime_type, data })
+            } else {
+                Ok(ImageSource::File(base_dir.join(percent_decode(uri)?)))
+            }
+        }
+    }
+}
+
+/// Undoes the %XX escapes of a relative URI
+fn percent_decode(uri: &str) -> Result<String, SceneError> {
+    let invalid = || SceneError::Gltf(format!("invalid URI '{}'", uri));
+    let mut bytes: Vec<u8> = Vec::with_capacity(uri.len());
+    let mut rest = uri.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = tail.get(..2).and_then(|h| std::str::from_utf8(h).ok()).ok_or_else(invalid)?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+fn pbr_material(m: &gltf::Material) -> PbrMaterial {
+    let pbr = m.pbr_metallic_roughness();
+    let image_of = |t: gltf::Texture| t.source().index();
+    PbrMaterial{
+        name: m.name().unwrap_or("").to_owned(),
+        base_color: pbr.base_color_factor(),
+        base_color_texture: pbr.base_color_texture().map(|i| image_of(i.texture())),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr.metallic_roughness_texture().map(|i| image_of(i.texture())),
+        normal_texture: m.normal_texture().map(|i| image_of(i.texture())),
+        occlusion_texture: m.occlusion_texture().map(|i| image_of(i.texture())),
+        emissive: m.emissive_factor(),
+        emissive_texture: m.emissive_texture().map(|i| image_of(i.texture())),
+        alpha_mode: match m.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: m.alpha_cutoff().unwrap_or(0.5),
+        double_sided: m.double_sided(),
+    }
+}
+
+/// A phong material that looks roughly like m, for code written for obj files
+fn phong_material(m: &PbrMaterial, images: &[ImageSource]) -> Material {
+    let mut phong = Material::new(&m.name);
+    phong.diffuse = [m.base_color[0], m.base_color[1], m.base_color[2]];
+    phong.dissolve = m.base_color[3];
+    let shine = 1.0 - m.roughness;
+    phong.specular = [shine, shine, shine];
+    phong.shininess = shine * shine * 1000.0;
+    phong.diffuse_map = m.base_color_texture.and_then(|i| match &images[i] {
+        ImageSource::File(p) => Some(p.clone()),
+        ImageSource::Embedded{ .. } => None
+    });
+    phong
+}
+
+/// Vertex data of one mesh while its primitives are added
+#[derive(Default)]
+struct MeshBuilder {
+    pos: Vec<[f32; 3]>,
+    tex: Option<Vec<[f32; 2]>>,
+    norm: Option<Vec<[f32; 3]>>,
+    tangent: Option<Vec<[f32; 4]>>,
+    color: Option<Vec<[f32; 4]>>,
+    indicies: Vec<usize>,
+}
+
+/// Appends new values to an attribute that might not exist yet, filling in defaults for the vertices before them
+fn append<A: Copy + Default>(attrib: &mut Option<Vec<A>>, values: Option<Vec<A>>, before: usize, count: usize) {
+    match (attrib.as_mut(), values) {
+        (Some(a), Some(v)) => a.extend(v),
+        (Some(a), None) => a.resize(before + count, A::default()),
+        (None, Some(v)) => {
+            let mut a = vec![A::default(); before];
+            a.extend(v);
+            *attrib = Some(a);
+        },
+        (None, None) => {}
+    }
+}
+
+fn to_attribs<T: ObjScalar + Default, const N: usize>(values: &[[f32; N]], dim: Dimension) -> VertexAttribs<T> {
+    let mut a = VertexAttribs::new(dim);
+    a.data = values.iter().flatten().map(|v| T::from_f64(*v as f64)).collect();
+    a
+}
+
+impl<T, I> Scene<T, I>
+where T: ObjScalar + Default, I: Copy + TryFrom<usize>{
+    /// Loads a .gltf or .glb file, primitives that aren't triangles or have no readable positions are skipped with a warning
+    pub fn load(path: &Path) -> Result<(Self, Vec<SceneError>), SceneError>{
+        let Gltf{ document, blob } = Gltf::open(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let buffers = gltf::import_buffers(&document, Some(base_dir), blob)?;
+        let mut warnings: Vec<SceneError> = Vec::new();
+
+        let images = document.images().map(|i| image_source(&i, &buffers, base_dir)).collect::<Result<Vec<_>, _>>()?;
+        let materials: Vec<PbrMaterial> = document.materials().map(|m| pbr_material(&m)).collect();
+        let phong: Vec<Material> = materials.iter().map(|m| phong_material(m, &images)).collect();
+
+        let mut meshes: Vec<ObjData<T, I>> = Vec::new();
+        for mesh in document.meshes() {
+            let mut b = MeshBuilder::default();
+            let mut ranges: Vec<(Option<usize>, std::ops::Range<usize>)> = Vec::new();
+            for prim in mesh.primitives() {
+                let reader = prim.reader(|buffer| Some(&buffers[buffer.index()]));
+                let pos: Vec<[f32; 3]> = match reader.read_positions() {
+                    Some(p) => p.collect(),
+                    None => {
+                        warnings.push(SceneError::MissingPositions{ mesh: mesh.index(), primitive: prim.index() });
+                        continue;
+                    }
+                };
+                let before = b.pos.len();
+                let count = pos.len();
+                let corners: Vec<usize> = match reader.read_indices() {
+                    Some(i) => i.into_u32().map(|i| i as usize).collect(),
+                    None => (0..count).collect()
+                };
+                let triangles: Vec<usize> = match prim.mode() {
+                    Mode::Triangles => corners,
+                    Mode::TriangleStrip => (2..corners.len()).flat_map(|i| {
+                        // Every other triangle is flipped to keep the winding
+                        if i % 2 == 0 { [corners[i - 2], corners[i - 1], corners[i]] } else { [corners[i - 1], corners[i - 2], corners[i]] }
+                    }).collect(),
+                    Mode::TriangleFan => (2..corners.len()).flat_map(|i| [corners[0], corners[i - 1], corners[i]]).collect(),
+                    mode => {
+                        warnings.push(SceneError::UnsupportedPrimitive{ mesh: mesh.index(), primitive: prim.index(), mode: format!("{:?}", mode) });
+                        continue;
+                    }
+                };
+                if triangles.iter().any(|i| *i >= count) {
+                    return Err(SceneError::Gltf(format!("primitive {} of mesh {} has an index that is out of range", prim.index(), mesh.index())));
+                }
+
+                b.pos.extend(pos);
+                append(&mut b.tex, reader.read_tex_coords(0).map(|t| t.into_f32().collect()), before, count);
+                append(&mut b.norm, reader.read_normals().map(|n| n.collect()), before, count);
+                append(&mut b.tangent, reader.read_tangents().map(|t| t.collect()), before, count);
+                append(&mut b.color, reader.read_colors(0).map(|c| c.into_rgba_f32().collect()), before, count);
+                let start = b.indicies.len();
+                b.indicies.extend(triangles.iter().map(|i| i + before));
+                ranges.push((prim.material().index(), start..b.indicies.len()));
+            }
+
+            let mut o = ObjData::<T, I>{
+                pos_data: to_attribs(&b.pos, Dimension::ThreeDim),
+                tex_data: b.tex.as_ref().map(|t| to_attribs(t, Dimension::TwoDim)),
+                norm_data: b.norm.as_ref().map(|n| to_attribs(n, Dimension::ThreeDim)),
+                tangent_data: b.tangent.as_ref().map(|t| to_attribs(t, Dimension::FourDim)),
+                color_data: b.color.as_ref().map(|c| to_attribs(c, Dimension::FourDim)),
+                indicies: b.indicies.iter().map(|i| I::try_from(*i).map_err(|_| SceneError::IndicesTooBig)).collect::<Result<_, _>>()?,
+                line_indicies: Vec::new(),
+                point_indicies: Vec::new(),
+                mtl_libs: Vec::new(),
+                materials: phong.clone(),
+                material_ranges: Vec::new(),
+                sub_meshes: Vec::new(),
+                normal_generation: None,
+                weld: WeldTolerance::default(),
+            };
+            let object = mesh.name().unwrap_or("").to_owned();
+            for (material, range) in ranges {
+                let material_name = material.map(|m| materials[m].name.clone());
+                o.sub_meshes.push(SubMesh{ object: object.clone(), group: String::new(), material_name: material_name.clone(), material, range: range.clone() });
+                if let Some(name) = material_name {
+                    o.material_ranges.push(MaterialRange{ name, material, range });
+                }
+            }
+            meshes.push(o);
+        }
+
+        let nodes: Vec<SceneNode> = document.nodes().map(|n| SceneNode{
+            name: n.name().unwrap_or("").to_owned(),
+            transform: Mat4::from_cols_array_2d(&n.transform().matrix()),
+            mesh: n.mesh().map(|m| m.index()),
+            children: n.children().map(|c| c.index()).collect(),
+        }).collect();
+        let roots: Vec<usize> = match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(s) => s.nodes().map(|n| n.index()).collect(),
+            // Without scenes every node that isn't a child is a root
+            None => (0..nodes.len()).filter(|i| !nodes.iter().any(|n| n.children.contains(i))).collect()
+        };
+
+        Ok((Self{ meshes, nodes, roots, materials, images }, warnings))
+    }
+
+    /// Transform of every node relative to the scene, by multiplying the transforms of its parents
+    pub fn world_transforms(&self) -> Result<Vec<Mat4>, SceneError>{
+        let mut out = vec![Mat4::IDENTITY; self.nodes.len()];
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack: Vec<(usize, Mat4)> = self.roots.iter().map(|r| (*r, Mat4::IDENTITY)).collect();
+        while let Some((n, parent)) = stack.pop() {
+            if visited[n] {
+                return Err(SceneError::NodeCycle(n));
+            }
+            visited[n] = true;
+            out[n] = parent * self.nodes[n].transform;
+            stack.extend(self.nodes[n].children.iter().map(|c| (*c, out[n])));
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(children: Vec<usize>) -> SceneNode {
+        SceneNode{ name: String::new(), transform: Mat4::from_translation(glam::Vec3::X), mesh: None, children }
+    }
+
+    #[test]
+    fn world_transforms_detect_cycles() {
+        let mut scene: Scene<f32, u32> = Scene{ meshes: Vec::new(), nodes: vec![node(vec![1]), node(vec![2]), node(Vec::new())], roots: vec![0], materials: Vec::new(), images: Vec::new() };
+        let world = scene.world_transforms().unwrap();
+        assert_eq!(world[2].transform_point3(glam::Vec3::ZERO), glam::Vec3::new(3.0, 0.0, 0.0));
+        scene.nodes[2].children.push(0);
+        assert_eq!(scene.world_transforms(), Err(SceneError::NodeCycle(0)));
+    }
+
+    #[test]
+    fn load_reports_primitives_without_positions_and_decodes_uris() {
+        let gltf = r#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{ "byteLength": 36, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/" }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "accessors": [{ "bufferView": 0, "byteOffset": 24, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [0, 0, 1] }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }],
+            "images": [{ "uri": "data:image/png;base64,AAEC" }, { "uri": "my%20texture.png" }, { "uri": "data:image/jpeg;base64,AAECAw==" }]
+        }"#;
+        let dir = std::env::temp_dir().join(format!("gl-puck-scene-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.gltf");
+        std::fs::write(&path, gltf).unwrap();
+        let (scene, warnings) = Scene::<f32, u32>::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(warnings, vec![SceneError::MissingPositions{ mesh: 0, primitive: 0 }]);
+        assert!(scene.meshes[0].indicies.is_empty());
+        assert_eq!(scene.images, vec![
+            ImageSource::Embedded{ mime_type: "image/png".to_owned(), data: vec![0, 1, 2] },
+            ImageSource::File(dir.join("my texture.png")),
+            ImageSource::Embedded{ mime_type: "image/jpeg".to_owned(), data: vec![0, 1, 2, 3] },
+        ]);
+    }
+}
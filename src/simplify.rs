@@ -0,0 +1,513 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::convert::{TryFrom, TryInto};
+
+use crate::obj::{ObjData, ObjError, ObjErrorKind, ObjScalar, VertexAttribs};
+use crate::vector::{cross, dot, normalize, sub};
+
+/// When `ObjData::simplify` stops, whichever is reached first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimplifyOptions {
+    /// Stop once there are at most this many triangles, 0 to only stop at max_error
+    pub target_triangles: usize,
+    /// Largest error a collapse may cause ( about how far the surface moves ), None for no limit
+    pub max_error: Option<f64>,
+}
+
+/// What `ObjData::simplify` did
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimplifyReport {
+    pub triangles_before: usize,
+    pub triangles_after: usize,
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    /// The biggest error of any collapse that was done, in the units of max_error
+    pub error: f64,
+}
+
+/// Sum of squared distances to planes ( Garland and Heckbert 1997 ), the upper triangle of the 4x4 matrix
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// The plane through point with the unit normal n, weight scales the squared distance
+    fn from_plane(n: [f64; 3], point: [f64; 3], weight: f64) -> Self {
+        let [a, b, c] = n;
+        let d = -dot(n, point);
+        Self([a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|v| v * weight))
+    }
+
+    fn add(&mut self, o: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(&o.0) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: [f64; 3]) -> f64 {
+        let q = &self.0;
+        let [x, y, z] = p;
+        let e = q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x
+            + q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y
+            + q[7] * z * z + 2.0 * q[8] * z
+            + q[9];
+        e.max(0.0)
+    }
+}
+
+/// How much more borders and UV seams weigh than the faces
+const EDGE_WEIGHT: f64 = 10.0;
+
+/// A possible collapse of the position from into to, cheapest first in a `BinaryHeap`
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    /// `Simplifier::version` of from and to when the cost was computed
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, o: &Self) -> bool {
+        self.cmp(o) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, o: &Self) -> Option<Ordering> {
+        Some(self.cmp(o))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, o: &Self) -> Ordering {
+        o.cost.partial_cmp(&self.cost).unwrap_or(Ordering::Equal).then_with(|| (o.from, o.to).cmp(&(self.from, self.to)))
+    }
+}
+
+/// A side of a triangle as its positions, the triangle and its vertices, each pair smallest first
+type TriangleSide = ((usize, usize), usize, (usize, usize));
+
+/// Triangles of vertices, where vertices with the same position ( the two sides of a UV seam ) are moved together
+struct Simplifier<'a> {
+    /// Of every position, not of every vertex
+    positions: Vec<[f64; 3]>,
+    position_of: Vec<usize>,
+    triangles: Vec<[usize; 3]>,
+    removed: Vec<bool>,
+    /// Triangles that use every position, may also contain removed ones
+    triangles_of: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    locked: &'a [bool],
+    /// Changes whenever the quadric of a position grows or the position goes away, to skip collapses that were computed before
+    version: Vec<u32>,
+}
+
+impl<'a> Simplifier<'a> {
+    fn live_triangles(&self, p: usize) -> impl Iterator<Item = usize> + '_ {
+        self.triangles_of[p].iter().copied().filter(move |t| !self.removed[*t])
+    }
+
+    fn corner_positions(&self, t: usize) -> [usize; 3] {
+        self.triangles[t].map(|v| self.position_of[v])
+    }
+
+    /// Positions sharing a triangle with p and how many triangles use the edge to each, sorted
+    fn neighbours(&self, p: usize) -> Vec<(usize, usize)> {
+        let mut all: Vec<usize> = self.live_triangles(p).flat_map(|t| self.corner_positions(t)).filter(|q| *q != p).collect();
+        all.sort_unstable();
+        let mut n: Vec<(usize, usize)> = Vec::with_capacity(all.len() / 2);
+        for q in all {
+            match n.last_mut() {
+                Some((r, count)) if *r == q => *count += 1,
+                _ => n.push((q, 1))
+            }
+        }
+        n
+    }
+
+    /// The vertex at to that each vertex at from moves onto, None if one has none or several
+    fn partners(&self, from: usize, to: usize) -> Option<Vec<(usize, usize)>> {
+        let mut partners: Vec<(usize, Option<usize>)> = Vec::new();
+        for t in self.live_triangles(from) {
+            let tri = self.triangles[t];
+            let at_from = tri.iter().copied().find(|v| self.position_of[*v] == from)?;
+            let at_to = tri.iter().copied().find(|v| self.position_of[*v] == to);
+            let i = match partners.iter().position(|(v, _)| *v == at_from) {
+                Some(i) => i,
+                None => { partners.push((at_from, None)); partners.len() - 1 }
+            };
+            match (partners[i].1, at_to) {
+                (Some(a), Some(b)) if a != b => return None,
+                (None, Some(b)) => partners[i].1 = Some(b),
+                _ => {}
+            }
+        }
+        partners.into_iter().map(|(v, p)| p.map(|p| (v, p))).collect()
+    }
+
+    /// The cost of moving from onto to, None if that would change the topology, a border or a seam, or flip a triangle
+    fn evaluate(&self, from: usize, to: usize) -> Option<f64> {
+        if self.locked[from] {
+            return None;
+        }
+        let from_neighbours = self.neighbours(from);
+        let shared = from_neighbours.iter().find(|(q, _)| *q == to)?.1;
+        if shared > 2 || from_neighbours.iter().any(|(_, n)| *n > 2) {
+            return None;
+        }
+        // A border position may only move along the border, and only if it's on one border
+        let border_edges = from_neighbours.iter().filter(|(_, n)| *n == 1).count();
+        if border_edges > 2 || (border_edges > 0 && shared != 1) {
+            return None;
+        }
+        // Only the positions opposite the collapsed edge may be neighbours of both, otherwise the mesh would fold onto itself
+        let to_neighbours = self.neighbours(to);
+        if from_neighbours.iter().filter(|(q, _)| to_neighbours.binary_search_by_key(q, |(r, _)| *r).is_ok()).count() > shared {
+            return None;
+        }
+        self.partners(from, to)?;
+
+        for t in self.live_triangles(from) {
+            let c = self.corner_positions(t);
+            if c.contains(&to) {
+                continue;
+            }
+            let before = c.map(|p| self.positions[p]);
+            let after = c.map(|p| if p == from { self.positions[to] } else { self.positions[p] });
+            let n_before = cross(sub(before[1], before[0]), sub(before[2], before[0]));
+            let n_after = cross(sub(after[1], after[0]), sub(after[2], after[0]));
+            if dot(n_before, n_after) <= 0.0 {
+                return None;
+            }
+        }
+        Some(self.quadrics[from].error(self.positions[to]))
+    }
+
+    /// Only the cost, whether the collapse is allowed is checked when it comes out of the heap, most never do
+    fn push_collapse(&self, from: usize, to: usize, heap: &mut BinaryHeap<Collapse>) {
+        if !self.locked[from] {
+            let cost = self.quadrics[from].error(self.positions[to]);
+            heap.push(Collapse{ cost, from, to, versions: (self.version[from], self.version[to]) });
+        }
+    }
+
+    fn push_collapses(&self, p: usize, heap: &mut BinaryHeap<Collapse>) {
+        for (q, _) in self.neighbours(p) {
+            self.push_collapse(p, q, heap);
+            self.push_collapse(q, p, heap);
+        }
+    }
+
+    /// Moves every vertex at from to its partner at to, returns how many triangles were removed
+    fn collapse(&mut self, from: usize, to: usize) -> usize {
+        let partners = match self.partners(from, to) {
+            Some(p) => p,
+            None => return 0
+        };
+        let mut removed = 0;
+        let around: Vec<usize> = self.live_triangles(from).collect();
+        for t in around {
+            if self.corner_positions(t).contains(&to) {
+                self.removed[t] = true;
+                removed += 1;
+                continue;
+            }
+            for v in self.triangles[t].iter_mut() {
+                if let Some((_, p)) = partners.iter().find(|(w, _)| *w == *v) {
+                    *v = *p;
+                }
+            }
+            self.triangles_of[to].push(t);
+        }
+        self.triangles_of[from].clear();
+        let q = self.quadrics[from];
+        self.quadrics[to].add(&q);
+
+        let removed_flags = &self.removed;
+        self.triangles_of[to].retain(|t| !removed_flags[*t]);
+        self.version[to] += 1;
+        self.version[from] += 1;
+        removed
+    }
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy + TryInto<usize> + TryFrom<usize> {
+    /// Collapses edges, cheapest first, until options says to stop. Seams and borders only collapse along themselves, line and point vertices stay.
+    pub fn simplify(&mut self, options: &SimplifyOptions) -> Result<SimplifyReport, ObjError> {
+        let too_big = || ObjError::new(0, "", ObjErrorKind::IndicesTooBig);
+        self.check_attribute_counts()?;
+        let triangles = self.checked_triangles()?;
+        let vertex_count = self.pos_data.len();
+        let mut line_and_point: Vec<usize> = Vec::with_capacity(self.line_indicies.len() + self.point_indicies.len());
+        for i in self.line_indicies.iter().chain(&self.point_indicies) {
+            let i: usize = (*i).try_into().map_err(|_| too_big())?;
+            if i >= vertex_count {
+                return Err(ObjError::new(0, "", ObjErrorKind::IndexOutOfRange{ attribute: "position", index: i.to_string(), loaded: vertex_count }));
+            }
+            line_and_point.push(i);
+        }
+
+        // Vertices with exactly the same position are one position, the sides of a seam
+        let vertex_positions = self.positions_3d();
+        let mut position_ids: HashMap<[u64; 3], usize> = HashMap::new();
+        let mut positions: Vec<[f64; 3]> = Vec::new();
+        let position_of: Vec<usize> = vertex_positions.iter().map(|p| {
+            *position_ids.entry(p.map(|c| (c + 0.0).to_bits())).or_insert_with(|| { positions.push(*p); positions.len() - 1 })
+        }).collect();
+        let mut locked: Vec<bool> = vec![false; positions.len()];
+        for v in line_and_point {
+            locked[position_of[v]] = true;
+        }
+
+        let mut s = Simplifier{
+            triangles_of: vec![Vec::new(); positions.len()],
+            quadrics: vec![Quadric::default(); positions.len()],
+            version: vec![0; positions.len()],
+            removed: vec![false; triangles.len()],
+            positions,
+            position_of,
+            triangles,
+            locked: &locked,
+        };
+        // Triangles with a position twice have no area, they're removed
+        for t in 0..s.triangles.len() {
+            let c = s.corner_positions(t);
+            if c[0] == c[1] || c[1] == c[2] || c[0] == c[2] {
+                s.removed[t] = true;
+                continue;
+            }
+            for p in c {
+                s.triangles_of[p].push(t);
+            }
+        }
+
+        // Face planes, and planes perpendicular to the faces along borders and seams.
+        // Edges are sorted so the sides of an edge are next to each other
+        let mut edges: Vec<TriangleSide> = Vec::with_capacity(s.triangles.len() * 3);
+        for t in 0..s.triangles.len() {
+            if s.removed[t] {
+                continue;
+            }
+            let c = s.corner_positions(t);
+            let p = c.map(|i| s.positions[i]);
+            let n = match normalize(cross(sub(p[1], p[0]), sub(p[2], p[0]))) {
+                Some(n) => n,
+                None => continue
+            };
+            let q = Quadric::from_plane(n, p[0], 1.0);
+            for i in c {
+                s.quadrics[i].add(&q);
+            }
+            for k in 0..3 {
+                let (a, b) = (k, (k + 1) % 3);
+                let key = (c[a].min(c[b]), c[a].max(c[b]));
+                let (va, vb) = (s.triangles[t][a], s.triangles[t][b]);
+                edges.push((key, t, (va.min(vb), va.max(vb))));
+            }
+        }
+        edges.sort_unstable();
+        let mut start = 0;
+        while start < edges.len() {
+            let (a, b) = edges[start].0;
+            let end = start + edges[start..].iter().take_while(|e| e.0 == (a, b)).count();
+            let users = &edges[start..end];
+            start = end;
+            let is_border = users.len() == 1;
+            let is_seam = users.len() == 2 && users[0].2 != users[1].2;
+            if !is_border && !is_seam {
+                continue;
+            }
+            for (_, t, _) in users {
+                let p = s.triangles[*t].map(|v| s.positions[s.position_of[v]]);
+                let face = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+                if let Some(n) = normalize(cross(sub(s.positions[b], s.positions[a]), face)) {
+                    let q = Quadric::from_plane(n, s.positions[a], EDGE_WEIGHT);
+                    s.quadrics[a].add(&q);
+                    s.quadrics[b].add(&q);
+                }
+            }
+        }
+
+        let triangles_before = s.triangles.len();
+        let mut triangle_count = s.removed.iter().filter(|r| !**r).count();
+        let mut heap: BinaryHeap<Collapse> = BinaryHeap::new();
+        for p in 0..s.positions.len() {
+            for (q, _) in s.neighbours(p).into_iter().filter(|(q, _)| *q > p) {
+                s.push_collapse(p, q, &mut heap);
+                s.push_collapse(q, p, &mut heap);
+            }
+        }
+        let mut error: f64 = 0.0;
+        while triangle_count > options.target_triangles {
+            let c = match heap.pop() {
+                Some(c) => c,
+                None => break
+            };
+            if (s.version[c.from], s.version[c.to]) != c.versions {
+                continue;
+            }
+            if options.max_error.is_some_and(|max| c.cost.sqrt() > max) {
+                break;
+            }
+            // The cost is still right, but the collapse may not be allowed
+            let cost = match s.evaluate(c.from, c.to) {
+                Some(cost) => cost,
+                None => continue
+            };
+            error = error.max(cost.sqrt());
+            triangle_count -= s.collapse(c.from, c.to);
+            s.push_collapses(c.to, &mut heap);
+        }
+
+        // Keep the remaining triangles in order and shrink the ranges with them
+        let mut kept_before: Vec<usize> = Vec::with_capacity(s.triangles.len() + 1);
+        kept_before.push(0);
+        for r in &s.removed {
+            kept_before.push(kept_before.last().unwrap() + if *r { 0 } else { 1 });
+        }
+        let new_index = |i: usize| kept_before[(i / 3).min(s.triangles.len())] * 3;
+        for m in &mut self.sub_meshes {
+            m.range = new_index(m.range.start)..new_index(m.range.end);
+        }
+        self.sub_meshes.retain(|m| !m.range.is_empty());
+        for m in &mut self.material_ranges {
+            m.range = new_index(m.range.start)..new_index(m.range.end);
+        }
+        self.material_ranges.retain(|m| !m.range.is_empty());
+        let triangles: Vec<[usize; 3]> = s.triangles.iter().zip(&s.removed).filter(|(_, r)| !**r).map(|(t, _)| *t).collect();
+
+        // Remove the vertices nothing uses anymore
+        let mut new_vertex: Vec<Option<usize>> = vec![None; vertex_count];
+        for v in triangles.iter().flatten() {
+            new_vertex[*v] = Some(0);
+        }
+        for i in self.line_indicies.iter().chain(&self.point_indicies) {
+            let i: usize = (*i).try_into().map_err(|_| too_big())?;
+            new_vertex[i] = Some(0);
+        }
+        let mut next = 0;
+        for v in new_vertex.iter_mut().flatten() {
+            *v = next;
+            next += 1;
+        }
+        let compact = |attribs: &VertexAttribs<T>| -> VertexAttribs<T> {
+            let mut out = VertexAttribs::new(attribs.get_elem_per_vert());
+            out.resize_to(next);
+            for (old, new) in new_vertex.iter().enumerate() {
+                if let Some(new) = new {
+                    out.set_slice(*new, attribs.get_slice(old));
+                }
+            }
+            out
+        };
+        self.pos_data = compact(&self.pos_data);
+        let others = self.tex_data.iter_mut().chain(self.norm_data.iter_mut()).chain(self.tangent_data.iter_mut()).chain(self.color_data.iter_mut());
+        for attribs in others {
+            *attribs = compact(attribs);
+        }
+        let remap = |i: usize| new_vertex[i].ok_or_else(too_big).and_then(|v| I::try_from(v).map_err(|_| too_big()));
+        self.indicies = triangles.iter().flatten().map(|v| remap(*v)).collect::<Result<_, _>>()?;
+        for i in self.line_indicies.iter_mut().chain(&mut self.point_indicies) {
+            let old: usize = (*i).try_into().map_err(|_| too_big())?;
+            *i = remap(old)?;
+        }
+
+        Ok(SimplifyReport{ triangles_before, triangles_after: triangles.len(), vertices_before: vertex_count, vertices_after: next, error })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::Dimension;
+
+    /// An n by n grid of quads with heights from z, split by a UV seam at x = n / 2 if seam is set
+    fn grid(n: usize, seam: bool, z: impl Fn(f64, f64) -> f64) -> ObjData<f64, u32> {
+        let mut o: ObjData<f64, u32> = ObjData::new(Dimension::ThreeDim, Some(Dimension::TwoDim), None);
+        let mut vertex = |x: usize, y: usize, right: bool| -> u32 {
+            let (fx, fy) = (x as f64, y as f64);
+            o.pos_data.data.extend_from_slice(&[fx, fy, z(fx, fy)]);
+            o.tex_data.as_mut().unwrap().data.extend_from_slice(&[fx / n as f64 + if right { 1.0 } else { 0.0 }, fy / n as f64]);
+            (o.pos_data.data.len() / 3 - 1) as u32
+        };
+        let mut ids = vec![[0u32; 2]; (n + 1) * (n + 1)];
+        for y in 0..=n {
+            for x in 0..=n {
+                let left = vertex(x, y, x > n / 2);
+                let right = if seam && x == n / 2 { vertex(x, y, true) } else { left };
+                ids[y * (n + 1) + x] = [left, right];
+            }
+        }
+        let mut indicies = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                // Quads right of the seam use the right side of the vertices on it
+                let side = if x >= n / 2 { 1 } else { 0 };
+                let v = |dx: usize, dy: usize| ids[(y + dy) * (n + 1) + x + dx][side];
+                indicies.extend_from_slice(&[v(0, 0), v(1, 0), v(1, 1), v(0, 0), v(1, 1), v(0, 1)]);
+            }
+        }
+        o.indicies = indicies;
+        o
+    }
+
+    fn triangle_normals(o: &ObjData<f64, u32>) -> Vec<[f64; 3]> {
+        let p = o.positions_3d();
+        o.indicies.chunks(3).map(|t| {
+            let c = [p[t[0] as usize], p[t[1] as usize], p[t[2] as usize]];
+            cross(sub(c[1], c[0]), sub(c[2], c[0]))
+        }).collect()
+    }
+
+    #[test]
+    fn reaches_the_target_without_flipping() {
+        let mut o = grid(12, false, |x, y| (x * 0.5).sin() * (y * 0.5).cos() * 0.5);
+        let report = o.simplify(&SimplifyOptions{ target_triangles: 60, max_error: None }).unwrap();
+        assert_eq!(report.triangles_before, 288);
+        assert_eq!(report.triangles_after, o.indicies.len() / 3);
+        assert!(report.triangles_after <= 60 && report.triangles_after >= 58, "{}", report.triangles_after);
+        assert_eq!(report.vertices_after, o.pos_data.len());
+        // Where a border corner was collapsed a sliver can stand upright, but nothing may face down
+        assert!(triangle_normals(&o).iter().all(|n| n[2] >= 0.0));
+    }
+
+    #[test]
+    fn keeps_borders_and_seams() {
+        let n = 8;
+        let mut o = grid(n, true, |_, _| 0.0);
+        let report = o.simplify(&SimplifyOptions{ target_triangles: 0, max_error: Some(1e-9) }).unwrap();
+        assert!(report.triangles_after < 20, "{}", report.triangles_after);
+        assert_eq!(report.error, 0.0);
+
+        // Nothing flipped and the square is still covered exactly, so its border didn't move
+        let normals = triangle_normals(&o);
+        assert!(normals.iter().all(|n| n[2] > 0.0));
+        let area: f64 = normals.iter().map(|n| n[2] / 2.0).sum();
+        assert!((area - (n * n) as f64).abs() < 1e-9);
+        let p = o.positions_3d();
+        for corner in [[0.0, 0.0], [8.0, 0.0], [0.0, 8.0], [8.0, 8.0], [4.0, 0.0], [4.0, 8.0]] {
+            assert!(p.iter().any(|q| q[0] == corner[0] && q[1] == corner[1]), "{:?}", corner);
+        }
+        // Every triangle is still on one side of the seam, with the texture coordinates of that side
+        let tex = o.tex_data.as_ref().unwrap();
+        for t in o.indicies.chunks(3) {
+            let right = tex.get_slice(t[0] as usize)[0] >= 1.0;
+            for v in t {
+                assert_eq!(tex.get_slice(*v as usize)[0] >= 1.0, right);
+                let x = p[*v as usize][0];
+                assert!(if right { x >= 4.0 } else { x <= 4.0 });
+            }
+        }
+    }
+
+    #[test]
+    fn attributes_must_match_the_positions() {
+        let mut o = grid(2, false, |_, _| 0.0);
+        o.tex_data.as_mut().unwrap().data.truncate(4);
+        let e = o.simplify(&SimplifyOptions{ target_triangles: 0, max_error: None }).unwrap_err();
+        assert_eq!(e.kind, ObjErrorKind::AttributeCountMismatch{ attribute: "texture coordinates", expected: 9, found: 2 });
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use crate::normals;
+use crate::obj::{self, ObjData, ObjError, ObjErrorKind, ObjScalar, ParseMode, SubMesh, VertexAttribs, WeldGrid};
+use crate::vector::{cross, normalize, sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StlFormat {
+    Ascii,
+    Binary,
+}
+
+/// One facet as it is stored in the file
+struct Facet {
+    normal: [f64; 3],
+    corners: [[f64; 3]; 3],
+}
+
+/// Facets and the solids ( name and first facet ) they belong to
+struct Solids {
+    facets: Vec<Facet>,
+    names: Vec<(String, usize)>,
+}
+
+fn face_normal(c: &[[f64; 3]; 3]) -> [f64; 3] {
+    normalize(cross(sub(c[1], c[0]), sub(c[2], c[0]))).unwrap_or([0.0; 3])
+}
+
+/// A binary file is exactly as long as its triangle count says, everything else that starts with "solid" is ascii
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() >= 84 {
+        let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as u64;
+        if 84 + 50 * count == bytes.len() as u64 {
+            return true;
+        }
+    }
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(bytes.len());
+    !bytes[start..].starts_with(b"solid")
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Solids, ObjError> {
+    if bytes.len() < 84 {
+        return Err(ObjError::new(0, "", ObjErrorKind::UnexpectedEof));
+    }
+    let count = u32::from_le_bytes(bytes[80..84].try_into().unwrap()) as usize;
+    let data = &bytes[84..];
+    if data.len() / 50 < count {
+        return Err(ObjError::new(0, &format!("facet {}", data.len() / 50), ObjErrorKind::UnexpectedEof));
+    }
+    let read = |b: &[u8], i: usize| -> [f64; 3] {
+        let f = |k: usize| f32::from_le_bytes(b[i + k * 4..i + k * 4 + 4].try_into().unwrap()) as f64;
+        [f(0), f(1), f(2)]
+    };
+    let facets = data.chunks_exact(50).take(count).map(|b| Facet{
+        normal: read(b, 0),
+        // The 2 bytes after the corners are an "attribute byte count" that nothing agrees on, so they're ignored
+        corners: [read(b, 12), read(b, 24), read(b, 36)],
+    }).collect();
+    Ok(Solids{ facets, names: vec![(String::new(), 0)] })
+}
+
+fn parse_ascii(text: &str, mode: ParseMode, warnings: &mut Vec<ObjError>) -> Result<Solids, ObjError> {
+    let mut solids = Solids{ facets: Vec::new(), names: Vec::new() };
+    let mut normal = [0.0; 3];
+    let mut corners: Vec<[f64; 3]> = Vec::with_capacity(3);
+    let mut in_facet = false;
+
+    let parse_vec = |args: &[&str]| -> Result<[f64; 3], ObjErrorKind> {
+        if args.len() != 3 {
+            return Err(ObjErrorKind::WrongArgumentCount(args.len()));
+        }
+        let mut v = [0.0; 3];
+        for (k, a) in args.iter().enumerate() {
+            v[k] = a.parse().map_err(|_| ObjErrorKind::InvalidNumber((*a).to_owned()))?;
+        }
+        Ok(v)
+    };
+
+    for (line_ind, full_line) in text.lines().enumerate() {
+        let parts: Vec<&str> = full_line.split_whitespace().collect();
+        let res = match parts.as_slice() {
+            [] => Ok(()),
+            ["solid", name @ ..] => {
+                solids.names.push((name.join(" "), solids.facets.len()));
+                Ok(())
+            },
+            ["endsolid", ..] | ["outer", "loop"] | ["endloop"] => Ok(()),
+            ["facet", "normal", n @ ..] => {
+                in_facet = true;
+                corners.clear();
+                parse_vec(n).map(|n| normal = n)
+            },
+            ["vertex", v @ ..] if in_facet => parse_vec(v).map(|v| corners.push(v)),
+            ["endfacet"] if in_facet => {
+                in_facet = false;
+                match corners.as_slice() {
+                    [a, b, c] => { solids.facets.push(Facet{ normal, corners: [*a, *b, *c] }); Ok(()) },
+                    _ if corners.len() < 3 => Err(ObjErrorKind::TooFewVertices(corners.len())),
+                    _ => Err(ObjErrorKind::WrongArgumentCount(corners.len()))
+                }
+            },
+            [keyword, ..] => Err(ObjErrorKind::UnknownStatement((*keyword).to_owned()))
+        };
+        if let Err(kind) = res {
+            // The rest of a broken facet is skipped
+            in_facet = false;
+            let e = ObjError::new(line_ind + 1, full_line, kind);
+            match mode {
+                ParseMode::Strict => return Err(e),
+                ParseMode::Lenient => warnings.push(e)
+            }
+        }
+    }
+    if solids.names.is_empty() {
+        solids.names.push((String::new(), 0));
+    }
+    Ok(solids)
+}
+
+impl<T, I> ObjData<T, I>
+where T: ObjScalar + Default, I: Copy + TryFrom<usize> + TryInto<usize>{
+    /// Replaces the mesh with an ascii or binary STL file, welding corners at the same position ( or within `weld.pos` )
+    pub fn load_stl<R: Read>(&mut self, mut r: R, mode: ParseMode) -> Result<Vec<ObjError>, ObjError>{
+        let mut bytes: Vec<u8> = Vec::new();
+        r.read_to_end(&mut bytes).map_err(|e| ObjError::new(0, "", ObjErrorKind::Io(e.kind())))?;
+        self.load_stl_from_bytes(&bytes, mode)
+    }
+
+    /// Loads the STL file at path, see `load_stl`
+    pub fn load_stl_from_path(&mut self, path: &Path, mode: ParseMode) -> Result<Vec<ObjError>, ObjError>{
+        let f = File::open(path).map_err(|e| ObjError::new(0, &path.to_string_lossy(), ObjErrorKind::Io(e.kind())))?;
+        self.load_stl(io::BufReader::new(f), mode)
+    }
+
+    /// Loads STL data that is already in memory, see `load_stl`
+    pub fn load_stl_from_bytes(&mut self, bytes: &[u8], mode: ParseMode) -> Result<Vec<ObjError>, ObjError>{
+        let mut warnings: Vec<ObjError> = Vec::new();
+        let solids = if is_binary(bytes) {
+            parse_binary(bytes)?
+        } else {
+            parse_ascii(&String::from_utf8_lossy(bytes), mode, &mut warnings)?
+        };
+
+        let with_norms = self.norm_data.is_some();
+        let corner_normals: Vec<[f64; 3]> = match (with_norms, self.normal_generation) {
+            (false, _) => Vec::new(),
+            (true, None) => solids.facets.iter().flat_map(|f| {
+                // Facet normals are often left as 0 by exporters
+                let n = if f.normal.iter().all(|c| c.is_finite()) && f.normal != [0.0; 3] { f.normal } else { face_normal(&f.corners) };
+                std::iter::repeat_n(n, 3)
+            }).collect(),
+            (true, Some(gen_mode)) => {
+                let mut positions: Vec<[f64; 3]> = Vec::new();
+                let mut grid = WeldGrid::new(self.weld.pos);
+                let triangles: Vec<[usize; 3]> = solids.facets.iter().map(|f| {
+                    let mut t = [0; 3];
+                    for (k, c) in f.corners.iter().enumerate() {
+                        t[k] = match grid.find(c, |id| obj::within(&positions[id], c, self.weld.pos)) {
+                            Some(id) => id,
+                            None => { grid.insert(c, positions.len()); positions.push(*c); positions.len() - 1 }
+                        };
+                    }
+                    t
+                }).collect();
+                let smooth_everything = vec![1; triangles.len()];
+                normals::generate_normals(&positions, &triangles, &smooth_everything, gen_mode)
+            }
+        };
+
+        let pos_dim = self.pos_data.get_elem_per_vert() as usize;
+        let norm_dim = self.norm_data.as_ref().map_or(0, |n| n.get_elem_per_vert() as usize);
+        let mut pos: Vec<T> = Vec::new();
+        let mut norm: Vec<T> = Vec::new();
+        // Position and normal of every vertex, as they were before conversion to T
+        let mut welded: Vec<([f64; 3], [f64; 3])> = Vec::new();
+        let mut grid = WeldGrid::new(self.weld.pos);
+        let mut indicies: Vec<I> = Vec::with_capacity(solids.facets.len() * 3);
+        for (t, f) in solids.facets.iter().enumerate() {
+            for (k, c) in f.corners.iter().enumerate() {
+                let n = corner_normals.get(t * 3 + k).copied().unwrap_or([0.0; 3]);
+                let same = |id: usize| obj::within(&welded[id].0, c, self.weld.pos) && (!with_norms || obj::within(&welded[id].1, &n, self.weld.norm));
+                let id = match grid.find(c, same) {
+                    Some(id) => id,
+                    None => {
+                        let padded_pos = [c[0], c[1], c[2], 1.0];
+                        pos.extend(padded_pos[..pos_dim].iter().map(|v| T::from_f64(*v)));
+                        let padded_norm = [n[0], n[1], n[2], 0.0];
+                        norm.extend(padded_norm[..norm_dim].iter().map(|v| T::from_f64(*v)));
+                        grid.insert(c, welded.len());
+                        welded.push((*c, n));
+                        welded.len() - 1
+                    }
+                };
+                indicies.push(I::try_from(id).map_err(|_| ObjError::new(0, "", ObjErrorKind::IndicesTooBig))?);
+            }
+        }
+
+        let vertex_count = welded.len();
+        self.pos_data.data = pos;
+        if let Some(n) = &mut self.norm_data {
+            n.data = norm;
+        }
+        if let Some(t) = &mut self.tex_data {
+            *t = VertexAttribs::new(t.get_elem_per_vert());
+            t.resize_to(vertex_count);
+        }
+        self.tangent_data = None;
+        self.color_data = None;
+        self.mtl_libs.clear();
+        self.materials.clear();
+        self.material_ranges.clear();
+        self.sub_meshes.clear();
+        for (i, (name, first)) in solids.names.iter().enumerate() {
+            let end = solids.names.get(i + 1).map_or(solids.facets.len(), |n| n.1);
+            if end > *first {
+                self.sub_meshes.push(SubMesh{ object: name.clone(), group: String::new(), material_name: None, material: None, range: first * 3..end * 3 });
+            }
+        }
+        self.indicies = indicies;
+        self.line_indicies.clear();
+        self.point_indicies.clear();
+        Ok(warnings)
+    }
+
+    /// Writes every triangle as a facet, ascii files get a solid per sub-mesh and one more for triangles outside of all sub-meshes
+    pub fn write_stl<W: Write>(&self, mut w: W, format: StlFormat) -> io::Result<()>{
+        let triangles = self.triangles()?;
+        let corner = |i: usize| -> [f64; 3] {
+            let p = self.pos_data.get_slice(i);
+            [p[0].to_f64(), p.get(1).map_or(0.0, |v| v.to_f64()), p.get(2).map_or(0.0, |v| v.to_f64())]
+        };
+        let facet = |t: &[usize; 3]| -> Facet {
+            let corners = [corner(t[0]), corner(t[1]), corner(t[2])];
+            Facet{ normal: face_normal(&corners), corners }
+        };
+
+        match format {
+            StlFormat::Binary => {
+                let mut header = [0u8; 80];
+                let text = b"binary STL written by gl-puck";
+                header[..text.len()].copy_from_slice(text);
+                w.write_all(&header)?;
+                let count = u32::try_from(triangles.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many triangles for binary STL"))?;
+                w.write_all(&count.to_le_bytes())?;
+                for t in &triangles {
+                    let f = facet(t);
+                    for v in f.normal.iter().chain(f.corners.iter().flatten()) {
+                        w.write_all(&(*v as f32).to_le_bytes())?;
+                    }
+                    w.write_all(&[0, 0])?;
+                }
+            },
+            StlFormat::Ascii => {
+                let mut covered = vec![false; triangles.len()];
+                for m in &self.sub_meshes {
+                    if m.range.start > m.range.end || m.range.end > self.indicies.len() || m.range.start % 3 != 0 || m.range.end % 3 != 0 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("sub-mesh {} has the invalid range {:?}", m.name(), m.range)));
+                    }
+                    covered[m.range.start / 3..m.range.end / 3].iter_mut().for_each(|c| *c = true);
+                }
+                let mut solids: Vec<(String, Vec<&[usize; 3]>)> = self.sub_meshes.iter()
+                    .map(|m| (m.name(), triangles[m.range.start / 3..m.range.end / 3].iter().collect()))
+                    .collect();
+                let uncovered: Vec<&[usize; 3]> = triangles.iter().zip(covered.iter()).filter(|(_, c)| !**c).map(|(t, _)| t).collect();
+                if !uncovered.is_empty() || solids.is_empty() {
+                    solids.push((String::new(), uncovered));
+                }
+                for (name, solid) in &solids {
+                    writeln!(w, "solid {}", name)?;
+                    for t in solid {
+                        let f = facet(t);
+                        writeln!(w, "  facet normal {} {} {}", f.normal[0] as f32, f.normal[1] as f32, f.normal[2] as f32)?;
+                        writeln!(w, "    outer loop")?;
+                        for c in &f.corners {
+                            writeln!(w, "      vertex {} {} {}", c[0] as f32, c[1] as f32, c[2] as f32)?;
+                        }
+                        writeln!(w, "    endloop")?;
+                        writeln!(w, "  endfacet")?;
+                    }
+                    writeln!(w, "endsolid {}", name)?;
+                }
+            }
+        }
+        w.flush()
+    }
+
+    pub fn save_stl(&self, path: &Path, format: StlFormat) -> io::Result<()>{
+        self.write_stl(io::BufWriter::new(File::create(path)?), format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::Dimension;
+
+    fn new_obj(norm: bool) -> ObjData<f32, u32> {
+        ObjData::new(Dimension::ThreeDim, None, if norm { Some(Dimension::ThreeDim) } else { None })
+    }
+
+    fn facet(normal: &str, corners: [&str; 3]) -> String {
+        format!("facet normal {}\nouter loop\nvertex {}\nvertex {}\nvertex {}\nendloop\nendfacet\n", normal, corners[0], corners[1], corners[2])
+    }
+
+    /// Two triangles of a unit square, folded along the diagonal if fold
+    fn square(normals: [&str; 2], fold: bool) -> String {
+        let far = if fold { "1 1 1" } else { "1 1 0" };
+        format!("solid square\n{}{}endsolid square\n", facet(normals[0], ["0 0 0", "1 0 0", far]), facet(normals[1], ["0 0 0", far, "0 1 0"]))
+    }
+
+    fn write(o: &ObjData<f32, u32>, format: StlFormat) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        o.write_stl(&mut bytes, format).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn ascii_and_binary_round_trips() {
+        let mut o = new_obj(true);
+        o.load_stl_from_bytes(square(["0 0 0"; 2], true).as_bytes(), ParseMode::Strict).unwrap();
+        assert_eq!((o.pos_data.len(), o.indicies.len()), (6, 6));
+        assert_eq!(o.sub_meshes.iter().map(|m| (m.name(), m.range.clone())).collect::<Vec<_>>(), [("square".to_owned(), 0..6)]);
+
+        let ascii = write(&o, StlFormat::Ascii);
+        assert!(!is_binary(&ascii));
+        let mut reloaded = new_obj(true);
+        assert!(reloaded.load_stl_from_bytes(&ascii, ParseMode::Strict).unwrap().is_empty());
+        assert!(reloaded == o);
+
+        let binary = write(&o, StlFormat::Binary);
+        assert_eq!(binary.len(), 84 + 50 * 2);
+        let mut reloaded = new_obj(true);
+        reloaded.load_stl_from_bytes(&binary, ParseMode::Strict).unwrap();
+        assert_eq!((&reloaded.pos_data, &reloaded.norm_data, &reloaded.indicies), (&o.pos_data, &o.norm_data, &o.indicies));
+    }
+
+    #[test]
+    fn detecting_the_format() {
+        // Ascii files start with "solid" and aren't as long as bytes 80..84 would say
+        let ascii = square(["0 0 1"; 2], false);
+        assert!(ascii.starts_with("solid") && !is_binary(ascii.as_bytes()));
+        assert!(!is_binary(format!("  \n{}", ascii).as_bytes()));
+
+        // Some exporters start the binary header with "solid" too
+        let mut o = new_obj(false);
+        o.load_stl_from_bytes(ascii.as_bytes(), ParseMode::Strict).unwrap();
+        let mut binary = write(&o, StlFormat::Binary);
+        binary[..6].copy_from_slice(b"solid ");
+        assert!(is_binary(&binary));
+        // Unless a truncated one, which can only be ascii
+        assert!(!is_binary(&binary[..binary.len() - 1]));
+        let mut reloaded = new_obj(false);
+        reloaded.load_stl_from_bytes(&binary, ParseMode::Lenient).unwrap();
+        assert_eq!(reloaded.indicies, o.indicies);
+    }
+
+    #[test]
+    fn welding_with_and_without_facet_normals() {
+        let load = |text: &str, norm: bool| {
+            let mut o = new_obj(norm);
+            o.load_stl_from_bytes(text.as_bytes(), ParseMode::Strict).unwrap();
+            (o.pos_data.len(), o.indicies)
+        };
+        // Corners of a flat square share their normal, so the diagonal is welded
+        assert_eq!(load(&square(["0 0 1"; 2], false), true), (4, vec![0, 1, 2, 0, 2, 3]));
+        // Missing facet normals are computed, which gives the same result
+        assert_eq!(load(&square(["0 0 0"; 2], false), true), (4, vec![0, 1, 2, 0, 2, 3]));
+        // Different facet normals keep the corners apart
+        assert_eq!(load(&square(["0 0 1", "0 0 -1"], false), true).0, 6);
+        assert_eq!(load(&square(["0 0 0"; 2], true), true).0, 6);
+        // Without normals only the positions matter
+        assert_eq!(load(&square(["0 0 1", "0 0 -1"], true), false), (4, vec![0, 1, 2, 0, 2, 3]));
+
+        // Corners within tolerance are welded even on both sides of a grid line
+        let straddling = format!("solid\n{}{}endsolid\n", facet("0 0 1", ["0 0 0", "1 0 0", "0.9999 1 0"]), facet("0 0 1", ["0 0 0", "1.0001 1 0", "0 1 0"]));
+        let mut o = new_obj(true);
+        o.weld.pos = Some(0.001);
+        o.load_stl_from_bytes(straddling.as_bytes(), ParseMode::Strict).unwrap();
+        assert_eq!((o.pos_data.len(), o.indicies), (4, vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(load(&straddling, true).0, 5);
+    }
+
+    #[test]
+    fn ascii_solids_cover_every_triangle() {
+        let mut o = new_obj(false);
+        o.load_stl_from_bytes(square(["0 0 1"; 2], false).as_bytes(), ParseMode::Strict).unwrap();
+        o.sub_meshes[0].range = 3..6;
+        let text = String::from_utf8(write(&o, StlFormat::Ascii)).unwrap();
+        assert_eq!(text.matches("endfacet").count(), 2);
+        assert!(text.starts_with("solid square\n") && text.contains("endsolid square\nsolid \n"));
+
+        o.sub_meshes[0].range = 3..9;
+        let mut bytes: Vec<u8> = Vec::new();
+        assert_eq!(o.write_stl(&mut bytes, StlFormat::Ascii).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        o.sub_meshes[0].range = 1..6;
+        assert_eq!(o.write_stl(&mut bytes, StlFormat::Ascii).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
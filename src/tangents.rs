@@ -0,0 +1,84 @@
+use bevy_mikktspace::Geometry;
+
+/// Triangles for `bevy_mikktspace`, which writes a tangent for every corner
+struct Triangles<'a> {
+    positions: &'a [[f64; 3]],
+    normals: &'a [[f64; 3]],
+    tex_coords: &'a [[f64; 2]],
+    triangles: &'a [[usize; 3]],
+    tangents: Vec<[f64; 4]>,
+}
+
+impl Geometry for Triangles<'_> {
+    fn num_faces(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.triangles[face][vert]].map(|c| c as f32)
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.triangles[face][vert]].map(|c| c as f32)
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.tex_coords[self.triangles[face][vert]].map(|c| c as f32)
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent.map(f64::from);
+    }
+}
+
+/// A MikkTSpace tangent and handedness ( w ) for every corner of triangles
+pub fn generate_tangents(positions: &[[f64; 3]], normals: &[[f64; 3]], tex_coords: &[[f64; 2]], triangles: &[[usize; 3]]) -> Vec<[f64; 4]> {
+    let mut t = Triangles{ positions, normals, tex_coords, triangles, tangents: vec![[1.0, 0.0, 0.0, 1.0]; triangles.len() * 3] };
+    // It only fails without triangles, which leaves nothing to write
+    bevy_mikktspace::generate_tangents(&mut t);
+    t.tangents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f64; 4], b: [f64; 4]) -> bool {
+        a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-6)
+    }
+
+    // The expected tangents follow from the MikkTSpace definition: dP/du, made perpendicular to the vertex normal
+
+    #[test]
+    fn skewed_texture_coordinates() {
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+        let tex_coords = [[0.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let tangents = generate_tangents(&positions, &[[0.0, 0.0, 1.0]; 3], &tex_coords, &[[0, 1, 2]]);
+        let h = std::f64::consts::FRAC_1_SQRT_2;
+        for t in tangents {
+            assert!(close(t, [h, -h, 0.0, 1.0]), "{:?}", t);
+        }
+    }
+
+    #[test]
+    fn projected_onto_the_vertex_normal() {
+        let positions = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        let tex_coords = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let triangles = [[0, 1, 2], [0, 2, 3]];
+        let tangents = generate_tangents(&positions, &[[0.6, 0.0, 0.8]; 4], &tex_coords, &triangles);
+        for t in tangents {
+            assert!(close(t, [0.8, 0.0, -0.6, 1.0]), "{:?}", t);
+        }
+
+        // Mirrored texture coordinates flip the tangent and the handedness
+        let mirrored: Vec<[f64; 2]> = tex_coords.iter().map(|t| [1.0 - t[0], t[1]]).collect();
+        let tangents = generate_tangents(&positions, &[[0.0, 0.0, 1.0]; 4], &mirrored, &triangles);
+        for t in tangents {
+            assert!(close(t, [-1.0, 0.0, 0.0, -1.0]), "{:?}", t);
+        }
+    }
+}
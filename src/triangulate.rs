@@ -0,0 +1,273 @@
+use std::fmt;
+
+use crate::vector::{cross, dot, length, sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriangulationError {
+    /// Less than 3 distinct points, or all of them on one line
+    Degenerate,
+    /// Two edges that aren't next to each other cross or touch
+    SelfIntersecting,
+}
+
+impl fmt::Display for TriangulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TriangulationError::Degenerate => write!(f, "degenerate polygon"),
+            TriangulationError::SelfIntersecting => write!(f, "self-intersecting polygon"),
+        }
+    }
+}
+
+impl std::error::Error for TriangulationError {}
+
+fn cross2(o: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Normal of the plane that fits the polygon best ( Newell's method ), its length is twice the area of the polygon
+pub fn polygon_normal(points: &[[f64; 3]]) -> [f64; 3] {
+    let mut n = [0.0; 3];
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        n[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        n[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        n[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    n
+}
+
+/// Segments ab and cd cross or touch
+fn segments_intersect(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2], eps: f64) -> bool {
+    let d1 = cross2(c, d, a);
+    let d2 = cross2(c, d, b);
+    let d3 = cross2(a, b, c);
+    let d4 = cross2(a, b, d);
+    if ((d1 > eps && d2 < -eps) || (d1 < -eps && d2 > eps)) && ((d3 > eps && d4 < -eps) || (d3 < -eps && d4 > eps)) {
+        return true;
+    }
+    let on_segment = |p: [f64; 2], q: [f64; 2], r: [f64; 2]| {
+        r[0] >= p[0].min(q[0]) && r[0] <= p[0].max(q[0]) && r[1] >= p[1].min(q[1]) && r[1] <= p[1].max(q[1])
+    };
+    (d1.abs() <= eps && on_segment(c, d, a))
+        || (d2.abs() <= eps && on_segment(c, d, b))
+        || (d3.abs() <= eps && on_segment(a, b, c))
+        || (d4.abs() <= eps && on_segment(a, b, d))
+}
+
+fn in_triangle(p: [f64; 2], a: [f64; 2], b: [f64; 2], c: [f64; 2], eps: f64) -> bool {
+    cross2(a, b, p) >= -eps && cross2(b, c, p) >= -eps && cross2(c, a, p) >= -eps
+}
+
+/// Ear clipping on the best-fit plane, triangles index into points and keep the polygon's winding
+pub fn triangulate(points: &[[f64; 3]]) -> Result<Vec<[usize; 3]>, TriangulationError> {
+    if points.len() < 3 {
+        return Err(TriangulationError::Degenerate);
+    }
+    let mut min = points[0];
+    let mut max = points[0];
+    for p in points {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+    }
+    let size = dot(sub(max, min), sub(max, min));
+    // Tolerance for areas ( cross products ), relative to the size of the polygon
+    let eps = size * 1e-12;
+
+    let mut n = polygon_normal(points);
+    let mut n_len = length(n);
+    if points.len() == 3 {
+        return if size == 0.0 || n_len <= eps { Err(TriangulationError::Degenerate) } else { Ok(vec![[0, 1, 2]]) };
+    }
+    if n_len <= eps {
+        // The area cancels out ( e.g. a bow tie ), so find the plane from the points instead
+        for i in 1..points.len() - 1 {
+            let c = cross(sub(points[i], points[0]), sub(points[i + 1], points[0]));
+            let l = length(c);
+            if l > n_len {
+                n = c;
+                n_len = l;
+            }
+        }
+    }
+    if size == 0.0 || n_len <= eps {
+        return Err(TriangulationError::Degenerate);
+    }
+
+    // Project onto the plane with a basis where u x v = n
+    let n = [n[0] / n_len, n[1] / n_len, n[2] / n_len];
+    let helper = if n[0].abs() < 0.9 { [1.0, 0.0, 0.0] } else { [0.0, 1.0, 0.0] };
+    let u = {
+        let c = cross(helper, n);
+        let l = length(c);
+        [c[0] / l, c[1] / l, c[2] / l]
+    };
+    let v = cross(n, u);
+
+    // Drop points equal to the one before them
+    let mut remaining: Vec<usize> = Vec::with_capacity(points.len());
+    let mut flat: Vec<[f64; 2]> = Vec::with_capacity(points.len());
+    for (i, p) in points.iter().enumerate() {
+        let q = [dot(*p, u), dot(*p, v)];
+        if let Some(last) = flat.last() {
+            if (q[0] - last[0]).powi(2) + (q[1] - last[1]).powi(2) <= eps {
+                continue;
+            }
+        }
+        remaining.push(i);
+        flat.push(q);
+    }
+    while flat.len() > 1 && (flat[0][0] - flat[flat.len() - 1][0]).powi(2) + (flat[0][1] - flat[flat.len() - 1][1]).powi(2) <= eps {
+        flat.pop();
+        remaining.pop();
+    }
+    if flat.len() < 3 {
+        return Err(TriangulationError::Degenerate);
+    }
+
+    let m = flat.len();
+    for i in 0..m {
+        for j in i + 2..m {
+            // The first and last edge are next to each other
+            if i == 0 && j == m - 1 {
+                continue;
+            }
+            if segments_intersect(flat[i], flat[(i + 1) % m], flat[j], flat[(j + 1) % m], eps) {
+                return Err(TriangulationError::SelfIntersecting);
+            }
+        }
+    }
+
+    // Make the polygon counter-clockwise in the plane
+    let area: f64 = (0..m).map(|i| cross2([0.0, 0.0], flat[i], flat[(i + 1) % m])).sum();
+    if area.abs() <= eps {
+        return Err(TriangulationError::Degenerate);
+    }
+    if area < 0.0 {
+        flat.iter_mut().for_each(|p| p[1] = -p[1]);
+    }
+
+    let mut poly: Vec<usize> = (0..m).collect();
+    let mut tris: Vec<[usize; 3]> = Vec::with_capacity(m - 2);
+    let mut i = 1 % poly.len();
+    let mut since_last_clip = 0;
+    while poly.len() > 3 {
+        let len = poly.len();
+        let (prev, cur, next) = (poly[(i + len - 1) % len], poly[i], poly[(i + 1) % len]);
+        let turn = cross2(flat[prev], flat[cur], flat[next]);
+        let is_ear = turn > eps
+            && !poly.iter().any(|&o| o != prev && o != cur && o != next && in_triangle(flat[o], flat[prev], flat[cur], flat[next], eps));
+        // Once no ears are left only collinear points can be removed, which doesn't change the shape
+        let is_collinear = since_last_clip >= len && turn.abs() <= eps;
+        if is_ear || is_collinear {
+            if is_ear {
+                tris.push([remaining[prev], remaining[cur], remaining[next]]);
+            }
+            poly.remove(i);
+            i %= poly.len();
+            since_last_clip = 0;
+        } else {
+            i = (i + 1) % len;
+            since_last_clip += 1;
+            if since_last_clip >= 2 * len {
+                return Err(TriangulationError::Degenerate);
+            }
+        }
+    }
+    let (a, b, c) = (poly[0], poly[1], poly[2]);
+    if cross2(flat[a], flat[b], flat[c]) > eps {
+        tris.push([remaining[a], remaining[b], remaining[c]]);
+    }
+    if tris.is_empty() {
+        return Err(TriangulationError::Degenerate);
+    }
+    Ok(tris)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(xy: &[[f64; 2]]) -> Vec<[f64; 3]> {
+        // Tilted, so the best-fit plane isn't just the xy plane
+        xy.iter().map(|p| [p[0], p[1], 0.5 * p[0]]).collect()
+    }
+
+    fn inside(xy: &[[f64; 2]], p: [f64; 2]) -> bool {
+        let mut inside = false;
+        for i in 0..xy.len() {
+            let (a, b) = (xy[i], xy[(i + 1) % xy.len()]);
+            if (a[1] > p[1]) != (b[1] > p[1]) && p[0] < a[0] + (p[1] - a[1]) / (b[1] - a[1]) * (b[0] - a[0]) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Triangulates xy and checks the winding, that every triangle is inside and that they cover the polygon once
+    fn check(xy: &[[f64; 2]], triangle_count: usize) -> Vec<[usize; 3]> {
+        let p = points(xy);
+        let tris = triangulate(&p).unwrap();
+        assert_eq!(tris.len(), triangle_count);
+        let n = polygon_normal(&p);
+        let mut area = 0.0;
+        for t in &tris {
+            let c = cross(sub(p[t[1]], p[t[0]]), sub(p[t[2]], p[t[0]]));
+            assert!(dot(c, n) > 0.0, "{:?} is wound the wrong way", t);
+            let centroid = [(xy[t[0]][0] + xy[t[1]][0] + xy[t[2]][0]) / 3.0, (xy[t[0]][1] + xy[t[1]][1] + xy[t[2]][1]) / 3.0];
+            assert!(inside(xy, centroid), "{:?} is outside", t);
+            area += length(c);
+        }
+        assert!((area - length(n)).abs() < 1e-9 * length(n));
+        tris
+    }
+
+    #[test]
+    fn concave_quad() {
+        // The reflex corner is 1, so splitting along 0-2 would cover the notch
+        let quad = [[0.0, 0.0], [2.0, 1.0], [4.0, 0.0], [2.0, 4.0]];
+        for t in check(&quad, 2) {
+            assert!(t.contains(&1) && t.contains(&3));
+        }
+        let reversed: Vec<[f64; 2]> = quad.iter().rev().copied().collect();
+        check(&reversed, 2);
+    }
+
+    #[test]
+    fn convex_and_concave_ngons() {
+        let circle = |n: usize, radius: &dyn Fn(usize) -> f64| -> Vec<[f64; 2]> {
+            (0..n).map(|i| {
+                let a = i as f64 / n as f64 * std::f64::consts::TAU;
+                [radius(i) * a.cos(), radius(i) * a.sin()]
+            }).collect()
+        };
+        check(&circle(7, &|_| 1.0), 5);
+        // A star with 6 points, every other corner is reflex
+        let star = circle(12, &|i| if i % 2 == 0 { 2.0 } else { 0.5 });
+        check(&star, 10);
+        let reversed: Vec<[f64; 2]> = star.iter().rev().copied().collect();
+        check(&reversed, 10);
+        // A comb, whose ears are only at the tips
+        check(&[[0.0, 0.0], [5.0, 0.0], [5.0, 3.0], [4.0, 3.0], [4.0, 1.0], [3.0, 1.0], [3.0, 3.0], [2.0, 3.0], [2.0, 1.0], [1.0, 1.0], [1.0, 3.0], [0.0, 3.0]], 10);
+    }
+
+    #[test]
+    fn collinear_and_degenerate() {
+        assert_eq!(triangulate(&points(&[[0.0, 0.0], [1.0, 1.0]])), Err(TriangulationError::Degenerate));
+        assert_eq!(triangulate(&points(&[[0.0, 0.0], [1.0, 1.0], [2.0, 2.0], [3.0, 3.0]])), Err(TriangulationError::Degenerate));
+        assert_eq!(triangulate(&points(&[[0.0, 0.0], [1.0, 0.0], [1.0, 0.0], [0.0, 0.0]])), Err(TriangulationError::Degenerate));
+        assert_eq!(triangulate(&[[1.0, 2.0, 3.0]; 3]), Err(TriangulationError::Degenerate));
+        // A repeated point is dropped, a point in the middle of an edge still gets a triangle
+        check(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [2.0, 2.0], [2.0, 2.0], [0.0, 2.0]], 3);
+    }
+
+    #[test]
+    fn bow_tie() {
+        assert_eq!(triangulate(&points(&[[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0]])), Err(TriangulationError::SelfIntersecting));
+        // Only the last edge crosses another one
+        let crossing = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [2.0, 4.0], [2.0, -1.0]];
+        assert_eq!(triangulate(&points(&crossing)), Err(TriangulationError::SelfIntersecting));
+    }
+}
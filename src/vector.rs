@@ -0,0 +1,35 @@
+// Small helpers for positions as [f64; 3], shared by the mesh processing modules
+
+pub(crate) fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn add_scaled(a: [f64; 3], b: [f64; 3], s: f64) -> [f64; 3] {
+    [a[0] + b[0] * s, a[1] + b[1] * s, a[2] + b[2] * s]
+}
+
+pub(crate) fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+pub(crate) fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn length(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+pub(crate) fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    length(sub(a, b))
+}
+
+/// None for zero length ( or non-finite ) vectors
+pub(crate) fn normalize(a: [f64; 3]) -> Option<[f64; 3]> {
+    let l = length(a);
+    if l > 0.0 && l.is_finite() {
+        Some([a[0] / l, a[1] / l, a[2] / l])
+    } else {
+        None
+    }
+}